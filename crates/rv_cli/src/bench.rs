@@ -0,0 +1,135 @@
+//! `rileyviewer bench`: publish synthetic plots at a fixed rate and report
+//! what the server sustained.
+//!
+//! The calling thread releases one publish every `1 / rate` seconds to a
+//! pool of workers, each doing a blocking [`rv_client::publish`]. Latency is
+//! timed per request, so a server that falls behind shows up as a low
+//! achieved rate and a long tail rather than as queueing in the numbers.
+
+use std::{
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use rv_core::PlotContent;
+
+/// Publishes in flight at once; enough to hold a few hundred plots a second
+/// against a local server without spawning a thread per request.
+const WORKERS: usize = 32;
+
+pub(crate) struct BenchArgs {
+    /// Plots per second
+    pub rate: u32,
+    pub duration: Duration,
+    /// Bytes of each plot's payload
+    pub size: usize,
+}
+
+struct Outcome {
+    latency: Duration,
+    error: Option<String>,
+}
+
+/// Parse a byte count like `512`, `10k`, or `1m` (binary units).
+pub(crate) fn parse_size(value: &str) -> Result<usize, String> {
+    let lower = value.trim().to_ascii_lowercase();
+    let (digits, multiplier) = match lower.strip_suffix(['k', 'm']) {
+        Some(digits) if lower.ends_with('k') => (digits, 1024),
+        Some(digits) => (digits, 1024 * 1024),
+        None => (lower.as_str(), 1),
+    };
+    digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid size {value:?}, expected e.g. 512, 10k, or 1m"))
+}
+
+pub(crate) fn run(base_url: &str, token: Option<&str>, args: &BenchArgs) -> Result<()> {
+    anyhow::ensure!(args.rate > 0, "--rate must be at least 1");
+    let total = (args.rate as f64 * args.duration.as_secs_f64()).round() as u64;
+    eprintln!(
+        "Publishing {total} plots of {} bytes at {}/s to {base_url}",
+        args.size, args.rate
+    );
+
+    let (jobs, queue) = mpsc::channel::<u64>();
+    let queue = Arc::new(Mutex::new(queue));
+    let (results, outcomes) = mpsc::channel::<Outcome>();
+    let workers: Vec<_> = (0..WORKERS)
+        .map(|_| {
+            let (queue, results) = (queue.clone(), results.clone());
+            let (base_url, token) = (base_url.to_string(), token.map(str::to_string));
+            let size = args.size;
+            thread::spawn(move || loop {
+                let Ok(n) = queue.lock().unwrap().recv() else {
+                    break;
+                };
+                let started = Instant::now();
+                let result = rv_client::publish(&base_url, token.as_deref(), plot(n, size));
+                let _ = results.send(Outcome {
+                    latency: started.elapsed(),
+                    error: result.err().map(|e| format!("{e:#}")),
+                });
+            })
+        })
+        .collect();
+    drop(results);
+
+    let started = Instant::now();
+    for n in 0..total {
+        let due = started + Duration::from_secs_f64(n as f64 / args.rate as f64);
+        if let Some(wait) = due.checked_duration_since(Instant::now()) {
+            thread::sleep(wait);
+        }
+        jobs.send(n)?;
+    }
+    drop(jobs);
+    let outcomes: Vec<Outcome> = outcomes.iter().collect();
+    let elapsed = started.elapsed();
+    for worker in workers {
+        let _ = worker.join();
+    }
+
+    print_summary(&outcomes, elapsed, args.rate);
+    Ok(())
+}
+
+/// A unique SVG padded to `size` bytes, so deduplication can't skip it.
+fn plot(n: u64, size: usize) -> PlotContent {
+    let svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\"><!-- bench {n} ");
+    let end = "--></svg>";
+    let padding = size.saturating_sub(svg.len() + end.len());
+    PlotContent::Svg(format!("{svg}{}{end}", "x".repeat(padding)))
+}
+
+fn print_summary(outcomes: &[Outcome], elapsed: Duration, rate: u32) {
+    let mut latencies: Vec<Duration> = outcomes
+        .iter()
+        .filter(|o| o.error.is_none())
+        .map(|o| o.latency)
+        .collect();
+    latencies.sort();
+    let ok = latencies.len();
+    let errors = outcomes.len() - ok;
+    let percentile = |p: f64| match latencies.len() {
+        0 => "-".to_string(),
+        len => {
+            let i = ((len as f64 * p).ceil() as usize).clamp(1, len) - 1;
+            format!("{:.1} ms", latencies[i].as_secs_f64() * 1000.0)
+        }
+    };
+    let achieved = ok as f64 / elapsed.as_secs_f64().max(f64::EPSILON);
+
+    println!("{:<12} {}", "published", ok);
+    println!("{:<12} {}", "errors", errors);
+    println!("{:<12} {:.1}s", "elapsed", elapsed.as_secs_f64());
+    println!("{:<12} {achieved:.1}/s (target {rate}/s)", "throughput");
+    println!("{:<12} {}", "p50", percentile(0.50));
+    println!("{:<12} {}", "p99", percentile(0.99));
+    if let Some(error) = outcomes.iter().find_map(|o| o.error.as_deref()) {
+        println!("{:<12} {error}", "first error");
+    }
+}
@@ -1,50 +1,341 @@
 use std::fs;
-use std::io::{Read, Write};
+use std::io::{BufRead, Read, Write};
 use std::net::SocketAddr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
-use rv_config::Config;
-use rv_server::{start_server_with, ServerConfig};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use rv_config::{
+    Config, DuplicateIds, Eviction, LogFormat, SlowConsumer, Transcode, UpstreamConfig,
+};
+use rv_core::{PlotContent, PlotMessage};
+use rv_server::{normalize_base_path, start_server_with, ServerConfig, ServerHandle, TokenConfig};
 use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+
+mod bench;
 
 #[derive(Parser)]
 #[command(name = "rileyviewer", about = "RileyViewer - Plot viewer for Python")]
 struct Cli {
+    /// Path to the server state file (defaults to the platform data directory)
+    #[arg(long, global = true, env = "RILEYVIEWER_STATE_FILE")]
+    state_file: Option<PathBuf>,
+    /// Log filter, e.g. "info" or "rv_server=debug" (overrides config file)
+    #[arg(long, global = true)]
+    log_level: Option<String>,
+    /// Log format: compact, pretty, or json (overrides config file)
+    #[arg(long, global = true)]
+    log_format: Option<LogFormat>,
     #[command(subcommand)]
     command: Command,
 }
 
+#[derive(Args)]
+struct ServeArgs {
+    /// Host to bind (overrides config file)
+    #[arg(long)]
+    host: Option<String>,
+    /// Port to bind (overrides config file)
+    #[arg(long)]
+    port: Option<u16>,
+    /// Serve admin routes on this loopback address instead, e.g. 127.0.0.1:8081 (overrides config file)
+    #[arg(long)]
+    admin_bind: Option<SocketAddr>,
+    /// Authentication token (auto-generated if not specified)
+    #[arg(long)]
+    token: Option<String>,
+    /// Token for the admin routes (overrides config file; auto-generated
+    /// unless they're on --admin-bind)
+    #[arg(long, env = "RILEYVIEWER_ADMIN_TOKEN", hide_env_values = true)]
+    admin_token: Option<String>,
+    /// Path to web dist directory (for development)
+    #[arg(long)]
+    dist_dir: Option<String>,
+    /// Directory whose files shadow the web UI's, for theming (overrides config file)
+    #[arg(long)]
+    overlay_dir: Option<String>,
+    /// Open browser automatically (overrides config file)
+    #[arg(long)]
+    open_browser: Option<bool>,
+    /// Maximum plots to keep in history, 0 for live only (overrides config file)
+    #[arg(long)]
+    history_limit: Option<usize>,
+    /// Serve everything under this path prefix, e.g. /viewer (overrides config file)
+    #[arg(long)]
+    base_path: Option<String>,
+    /// Drop WebSocket clients whose sends block this long, 0 to disable (overrides config file)
+    #[arg(long)]
+    send_timeout_secs: Option<u64>,
+    /// Reject publishes with 409 while no viewer is connected (overrides config file)
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    require_client: Option<bool>,
+    /// Add a header to every response, e.g. "X-Frame-Options: DENY" (repeatable; adds to config file)
+    #[arg(long = "header", value_name = "NAME: VALUE", value_parser = parse_header)]
+    headers: Vec<(String, String)>,
+    /// Add a CSP, X-Frame-Options, and other hardening headers to every response
+    /// (overrides config file)
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    secure_headers: Option<bool>,
+    /// Trust X-Forwarded-Proto from a reverse proxy for absolute links (overrides config file)
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    trust_proxy: Option<bool>,
+    /// Respawn the HTTP server if it panics, up to a few times a minute (overrides config file)
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    auto_restart: Option<bool>,
+    /// Allow binding a non-loopback host without a token
+    #[arg(long)]
+    insecure: bool,
+    /// Serve image thumbnails no larger than this many pixels (overrides config file)
+    #[arg(long)]
+    thumbnail_max_px: Option<u32>,
+    /// Persist history to this file across restarts, compressed if it ends in .gz or .zst (overrides config file)
+    #[arg(long)]
+    persist_path: Option<PathBuf>,
+    /// fsync persisted history writes (overrides config file)
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    persist_durable: Option<bool>,
+    /// Serve the plots saved in this NDJSON file (or .ndjson.gz, .ndjson.zst) (e.g. an old persist_path) without modifying it
+    #[arg(long)]
+    archive: Option<PathBuf>,
+    /// Refuse publishes and pin/unpin with 405, e.g. to browse an --archive
+    #[arg(long)]
+    read_only: bool,
+    /// Pretty-print JSON frames and responses for debugging (overrides config file)
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pretty_json: Option<bool>,
+    /// Ignore publishes identical to the newest plot (overrides config file)
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    dedup: Option<bool>,
+    /// Strip scripts and event handlers from published HTML (overrides config file)
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    sanitize_html: Option<bool>,
+    /// Lagging WebSocket clients: drop_oldest or disconnect (overrides config file)
+    #[arg(long)]
+    slow_consumer: Option<SlowConsumer>,
+    /// Publishes reusing an id already in history: reject or replace (overrides config file)
+    #[arg(long)]
+    duplicate_ids: Option<DuplicateIds>,
+    /// Plot evicted past the history limit: fifo or lru_viewed (overrides config file)
+    #[arg(long)]
+    eviction: Option<Eviction>,
+    /// Re-encode published PNGs before storing: webp or avif (overrides config file)
+    #[arg(long)]
+    transcode: Option<Transcode>,
+    /// Sign plots with HMAC-SHA256 under this key (overrides config file)
+    #[arg(long, env = "RILEYVIEWER_SIGN_KEY", hide_env_values = true)]
+    sign_key: Option<String>,
+    /// POST every published plot to this URL (overrides config file)
+    #[arg(long)]
+    webhook_url: Option<String>,
+    /// Bearer token for webhook requests (overrides config file)
+    #[arg(long, env = "RILEYVIEWER_WEBHOOK_TOKEN", hide_env_values = true)]
+    webhook_token: Option<String>,
+    /// Forward every plot to this rileyviewer, e.g. http://hub:8080 (overrides config file)
+    #[arg(long)]
+    upstream_url: Option<String>,
+    /// Token for the upstream rileyviewer (overrides config file)
+    #[arg(long, env = "RILEYVIEWER_UPSTREAM_TOKEN", hide_env_values = true)]
+    upstream_token: Option<String>,
+    /// Log every published plot to daily files named after this path; with no
+    /// path, audit.log in the data directory (overrides config file)
+    #[arg(long, value_name = "PATH", num_args = 0..=1)]
+    audit_log: Option<Option<PathBuf>>,
+    /// Recycle WebSocket connections after this many seconds (overrides config file)
+    #[arg(long)]
+    max_connection_secs: Option<u64>,
+    /// Serve the bundled web UI; false for API-only (overrides config file)
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    serve_spa: Option<bool>,
+    /// Move the viewer URL's token into a cookie and embed it in the page (overrides config file)
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    inject_token: Option<bool>,
+    /// Refuse to start if the web UI assets are missing
+    #[arg(long)]
+    strict_assets: bool,
+    /// Only accept these content types, comma-separated, e.g. Svg,Plotly (overrides config file)
+    #[arg(long, value_delimiter = ',')]
+    allowed_content_types: Option<Vec<String>>,
+    /// Print the effective settings (config file merged with flags) before starting
+    #[arg(long)]
+    print_config: bool,
+    /// Print a QR code of the viewer URL, for opening it on a phone or tablet
+    #[arg(long)]
+    qr: bool,
+    /// Shut down once the first viewer has connected and every viewer has left
+    #[arg(long)]
+    once: bool,
+    /// Also publish each NDJSON PlotMessage or PlotContent line read from stdin
+    #[arg(long)]
+    from_stdin: bool,
+    /// Shut down when stdin closes, instead of serving what it sent
+    #[arg(long, requires = "from_stdin")]
+    exit_on_eof: bool,
+}
+
+impl ServeArgs {
+    /// Merge these flags over the config file; flags win.
+    fn server_config(&self, config: &Config) -> ServerConfig {
+        let file = &config.server;
+        ServerConfig {
+            host: self.host.clone().unwrap_or_else(|| file.host.clone()),
+            port: self.port.unwrap_or(file.port),
+            admin_bind: self.admin_bind.or(file.admin_bind),
+            token: self.token.clone().into(),
+            admin_token: self
+                .admin_token
+                .clone()
+                .or_else(|| file.admin_token.clone()),
+            dist_dir: self.dist_dir.clone(),
+            overlay_dir: self
+                .overlay_dir
+                .clone()
+                .or_else(|| file.overlay_dir.clone()),
+            history_limit: self.history_limit.unwrap_or(file.history_limit),
+            base_path: self.base_path.clone().or_else(|| file.base_path.clone()),
+            strict_assets: self.strict_assets,
+            serve_spa: self.serve_spa.unwrap_or(file.serve_spa),
+            send_timeout_secs: self.send_timeout_secs.unwrap_or(file.send_timeout_secs),
+            require_client: self.require_client.unwrap_or(file.require_client),
+            auto_restart: self.auto_restart.unwrap_or(file.auto_restart),
+            insecure: self.insecure,
+            thumbnail_max_px: self.thumbnail_max_px.or(file.thumbnail_max_px),
+            // An archive replaces the configured history file rather than conflicting with it
+            persist_path: self
+                .persist_path
+                .clone()
+                .or_else(|| file.persist_path.clone().filter(|_| self.archive.is_none())),
+            persist_durable: self.persist_durable.unwrap_or(file.persist_durable),
+            archive_path: self.archive.clone(),
+            read_only: self.read_only,
+            inject_token: self.inject_token.unwrap_or(file.inject_token),
+            pretty_json: self.pretty_json.unwrap_or(file.pretty_json),
+            dedup: self.dedup.unwrap_or(file.dedup),
+            sanitize_html: self.sanitize_html.unwrap_or(file.sanitize_html),
+            slow_consumer: self.slow_consumer.unwrap_or(file.slow_consumer),
+            duplicate_ids: self.duplicate_ids.unwrap_or(file.duplicate_ids),
+            eviction: self.eviction.unwrap_or(file.eviction),
+            transcode: self.transcode.or(file.transcode),
+            render_hints: file.render_hints.clone(),
+            // Flags come last, so they win over the file for the same header
+            extra_headers: file
+                .extra_headers
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .chain(self.headers.iter().cloned())
+                .collect(),
+            secure_headers: self.secure_headers.unwrap_or(file.secure_headers),
+            trust_proxy: self.trust_proxy.unwrap_or(file.trust_proxy),
+            sign_key: self.sign_key.clone().or_else(|| file.sign_key.clone()),
+            webhook_url: self
+                .webhook_url
+                .clone()
+                .or_else(|| file.webhook_url.clone()),
+            webhook_token: self
+                .webhook_token
+                .clone()
+                .or_else(|| file.webhook_token.clone()),
+            upstream: self.upstream(file),
+            audit_log: match &self.audit_log {
+                Some(path) => Some(
+                    path.clone()
+                        .unwrap_or_else(|| rv_config::data_dir().join("audit.log")),
+                ),
+                None => file.audit_log.clone(),
+            },
+            max_connection_secs: self.max_connection_secs.or(file.max_connection_secs),
+            allowed_content_types: self
+                .allowed_content_types
+                .clone()
+                .or_else(|| file.allowed_content_types.clone()),
+        }
+    }
+
+    /// `--upstream-url` replaces the file's upstream; `--upstream-token` applies to either.
+    fn upstream(&self, file: &rv_config::ServerConfig) -> Option<UpstreamConfig> {
+        let mut upstream = match &self.upstream_url {
+            Some(url) => UpstreamConfig {
+                url: url.clone(),
+                token: None,
+            },
+            None => file.upstream.clone()?,
+        };
+        if self.upstream_token.is_some() {
+            upstream.token = self.upstream_token.clone();
+        }
+        Some(upstream)
+    }
+
+    fn open_browser(&self, config: &Config) -> bool {
+        self.open_browser.unwrap_or(config.server.open_browser)
+    }
+}
+
 #[derive(Subcommand)]
 enum Command {
     /// Start the viewer server
-    Serve {
-        /// Host to bind (overrides config file)
-        #[arg(long)]
-        host: Option<String>,
-        /// Port to bind (overrides config file)
-        #[arg(long)]
-        port: Option<u16>,
-        /// Authentication token (auto-generated if not specified)
-        #[arg(long)]
-        token: Option<String>,
-        /// Path to web dist directory (for development)
-        #[arg(long)]
-        dist_dir: Option<String>,
-        /// Open browser automatically (overrides config file)
-        #[arg(long)]
-        open_browser: Option<bool>,
-        /// Maximum plots to keep in history (overrides config file)
-        #[arg(long)]
-        history_limit: Option<usize>,
-    },
+    Serve(Box<ServeArgs>),
     /// Check if server is running
-    Status,
+    Status {
+        /// Output style: plain text, shell variable exports, or JSON
+        #[arg(long, value_enum, default_value_t = StatusFormat::Plain)]
+        format: StatusFormat,
+    },
     /// Stop the running server
     Stop,
+    /// Remove every plot from the running server's history, pinned ones included
+    Clear,
     /// Open browser for running server
     Open,
+    /// Print the running server's viewer URL, including the token
+    Url {
+        /// Also copy the URL to the clipboard
+        #[arg(long)]
+        copy: bool,
+    },
+    /// Print a one-line summary of each plot the running server sends
+    Tail {
+        /// Also print the first N characters of text content (SVG, HTML, JSON)
+        #[arg(long, value_name = "N")]
+        preview: Option<usize>,
+    },
+    /// Inspect the config file
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Publish synthetic plots to the running server and report throughput and latency
+    Bench {
+        /// Target plots per second
+        #[arg(long, default_value_t = 100)]
+        rate: u32,
+        /// Seconds to publish for
+        #[arg(long, default_value_t = 10)]
+        duration: u64,
+        /// Size of each plot, e.g. 512, 10k, or 1m
+        #[arg(long, default_value = "10k", value_parser = bench::parse_size)]
+        size: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Report every problem in the config file; exits nonzero if there are any
+    Validate {
+        /// Config file to check instead of the default location
+        path: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum StatusFormat {
+    Plain,
+    /// `export RV_ADDR=...` lines, e.g. for `eval "$(rileyviewer status --format env)"`
+    Env,
+    /// `set -gx RV_ADDR ...` lines, e.g. for `rileyviewer status --format fish | source`
+    Fish,
+    /// `$env:RV_ADDR = ...` lines, e.g. for
+    /// `rileyviewer status --format powershell | Out-String | Invoke-Expression`
+    Powershell,
+    Json,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -52,6 +343,41 @@ struct ServerState {
     pid: u32,
     addr: String,
     token: Option<String>,
+    /// For the admin routes, when it isn't `token`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    admin_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    base_path: Option<String>,
+    /// Where the admin routes listen when `admin_bind` moves them off `addr`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    admin_addr: Option<String>,
+}
+
+impl ServerState {
+    /// Root URL of the server, including any base path.
+    fn base_url(&self) -> String {
+        format!(
+            "http://{}{}",
+            self.addr,
+            self.base_path.as_deref().unwrap_or("")
+        )
+    }
+
+    /// Root URL of the admin routes: their own listener, or the server's.
+    fn admin_url(&self) -> String {
+        match &self.admin_addr {
+            Some(addr) => format!("http://{addr}"),
+            None => self.base_url(),
+        }
+    }
+
+    /// Browser URL for the viewer, with the token if one is set.
+    fn viewer_url(&self) -> String {
+        match self.token {
+            Some(ref t) => format!("{}/?token={}", self.base_url(), t),
+            None => format!("{}/", self.base_url()),
+        }
+    }
 }
 
 fn state_dir() -> PathBuf {
@@ -60,117 +386,257 @@ fn state_dir() -> PathBuf {
         .join("rileyviewer")
 }
 
-fn state_file() -> PathBuf {
+fn default_state_file() -> PathBuf {
     state_dir().join("server.json")
 }
 
-fn read_state() -> Option<ServerState> {
-    let path = state_file();
-    let mut file = fs::File::open(&path).ok()?;
+fn read_state(path: &Path) -> Option<ServerState> {
+    let mut file = fs::File::open(path).ok()?;
     let mut contents = String::new();
     file.read_to_string(&mut contents).ok()?;
     serde_json::from_str(&contents).ok()
 }
 
-fn write_state(state: &ServerState) -> Result<()> {
-    let dir = state_dir();
-    fs::create_dir_all(&dir).context("failed to create state directory")?;
-    let path = state_file();
-    let mut file = fs::File::create(&path).context("failed to create state file")?;
+fn write_state(path: &Path, state: &ServerState) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context("failed to create state directory")?;
+    }
+    let mut file = fs::File::create(path).context("failed to create state file")?;
     let json = serde_json::to_string_pretty(state)?;
     file.write_all(json.as_bytes())?;
     Ok(())
 }
 
-fn remove_state() {
-    let _ = fs::remove_file(state_file());
+fn remove_state(path: &Path) {
+    let _ = fs::remove_file(path);
 }
 
-fn check_server_running(addr: &str) -> bool {
-    let url = format!("http://{}/health", addr);
-    ureq::get(&url)
+/// Parse a `--header` of the form `Name: value`.
+fn parse_header(header: &str) -> Result<(String, String), String> {
+    let (name, value) = header
+        .split_once(':')
+        .ok_or_else(|| format!("expected NAME: VALUE, got {header:?}"))?;
+    Ok((name.trim().to_string(), value.trim().to_string()))
+}
+
+/// Whether a server answers `/health`. A draining one (503) still counts,
+/// so it can be stopped.
+fn check_server_running(base_url: &str) -> bool {
+    let url = format!("{}/health", base_url);
+    match ureq::get(&url)
         .timeout(std::time::Duration::from_millis(500))
         .call()
-        .is_ok()
+    {
+        Ok(_) | Err(ureq::Error::Status(503, _)) => true,
+        Err(_) => false,
+    }
+}
+
+/// Health checks [`wait_healthy`] makes before giving up.
+const HEALTH_ATTEMPTS: u32 = 3;
+/// Pause before the first retry, doubling after each.
+const HEALTH_BACKOFF: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// [`check_server_running`], retried briefly so a server that was only just
+/// started (e.g. detached by the Python client) isn't reported as dead and
+/// its state file removed.
+fn wait_healthy(base_url: &str) -> bool {
+    let mut backoff = HEALTH_BACKOFF;
+    for attempt in 0..HEALTH_ATTEMPTS {
+        if attempt > 0 {
+            std::thread::sleep(backoff);
+            backoff *= 2;
+        }
+        if check_server_running(base_url) {
+            return true;
+        }
+    }
+    false
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
-    let config = Config::load();
+    let strict = load_config_strict();
+    let config = match &strict {
+        Ok(config) => config.clone(),
+        Err(_) => Config::load(),
+    };
+    init_logging(
+        cli.log_level.or(config.logging.level.clone()),
+        cli.log_format.unwrap_or(config.logging.format),
+    );
+    let state_path = cli.state_file.unwrap_or_else(default_state_file);
 
     match cli.command {
-        Command::Serve { host, port, token, dist_dir, open_browser, history_limit } => {
-            // CLI flags override config file values
-            let host = host.unwrap_or(config.server.host);
-            let port = port.unwrap_or(config.server.port);
-            let open_browser = open_browser.unwrap_or(config.server.open_browser);
-            let history_limit = history_limit.unwrap_or(config.server.history_limit);
-            serve(host, port, token, dist_dir, open_browser, history_limit).await?
+        Command::Serve(args) => {
+            if let Err(problems) = &strict {
+                print_config_problems(&rv_config::config_file_path(), problems);
+            }
+            serve(&state_path, &args, &config).await?
         }
-        Command::Status => status()?,
-        Command::Stop => stop()?,
-        Command::Open => open()?,
+        Command::Status { format } => status(&state_path, format)?,
+        Command::Stop => stop(&state_path)?,
+        Command::Clear => clear(&state_path)?,
+        Command::Open => open(&state_path)?,
+        Command::Url { copy } => url(&state_path, copy)?,
+        Command::Tail { preview } => tail(&state_path, preview)?,
+        Command::Config(ConfigCommand::Validate { path }) => validate_config(path),
+        Command::Bench {
+            rate,
+            duration,
+            size,
+        } => bench(
+            &state_path,
+            &bench::BenchArgs {
+                rate,
+                duration: std::time::Duration::from_secs(duration),
+                size,
+            },
+        )?,
     }
     Ok(())
 }
 
+/// The config file, or every problem with it; no file at all means defaults.
+fn load_config_strict() -> Result<Config, Vec<rv_config::ConfigProblem>> {
+    let path = rv_config::config_file_path();
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+    Config::load_strict(&path)
+}
+
+fn print_config_problems(path: &Path, problems: &[rv_config::ConfigProblem]) {
+    eprintln!("{} has {} problem(s):", path.display(), problems.len());
+    for problem in problems {
+        eprintln!("  {problem}");
+    }
+}
+
+/// Install the tracing subscriber.
+///
+/// With no explicit level we fall back to `RUST_LOG`, and if that isn't set
+/// either no subscriber is installed (logging stays off).
+fn init_logging(level: Option<String>, format: LogFormat) {
+    use tracing_subscriber::EnvFilter;
+
+    let filter = match level {
+        Some(level) => EnvFilter::new(level),
+        None => match EnvFilter::try_from_default_env() {
+            Ok(filter) => filter,
+            Err(_) => return,
+        },
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+    match format {
+        LogFormat::Compact => builder.compact().init(),
+        LogFormat::Pretty => builder.pretty().init(),
+        LogFormat::Json => builder.json().init(),
+    }
+}
+
 fn generate_token() -> String {
     uuid::Uuid::new_v4().simple().to_string()
 }
 
-async fn serve(host: String, port: u16, token: Option<String>, dist_dir: Option<String>, open_browser: bool, history_limit: usize) -> Result<()> {
+/// The settings `serve` actually runs with, after merging the config file and flags.
+#[derive(Serialize)]
+struct EffectiveConfig<'a> {
+    open_browser: bool,
+    #[serde(flatten)]
+    server: &'a ServerConfig,
+}
+
+fn print_effective_config(config: &ServerConfig, open_browser: bool) -> Result<()> {
+    let effective = EffectiveConfig {
+        open_browser,
+        server: config,
+    };
+    println!("# Effective configuration");
+    println!(
+        "{}",
+        toml::to_string_pretty(&effective).context("failed to render config")?
+    );
+    Ok(())
+}
+
+async fn serve(state_path: &Path, args: &ServeArgs, file_config: &Config) -> Result<()> {
+    let mut config = args.server_config(file_config);
+    let open_browser = args.open_browser(file_config);
+
     // Check if already running
-    if let Some(state) = read_state() {
-        if check_server_running(&state.addr) {
-            println!("Server already running at http://{}", state.addr);
+    if let Some(state) = read_state(state_path) {
+        if check_server_running(&state.base_url()) {
+            println!("Server already running at {}", state.base_url());
             return Ok(());
         }
         // Stale state file, remove it
-        remove_state();
+        remove_state(state_path);
     }
 
     // Generate token upfront if not provided
-    let token = token.or_else(|| Some(generate_token()));
-    let addr_str = format!("{}:{}", host, port);
+    if config.token == TokenConfig::Auto {
+        config.token = TokenConfig::Fixed(generate_token());
+    }
+    // Likewise the admin token, unless the admin routes get their own listener
+    if config.admin_token.is_none() && config.admin_bind.is_none() {
+        config.admin_token = Some(generate_token());
+    }
+    config.base_path = config.base_path.as_deref().and_then(normalize_base_path);
+    let token = config.token.fixed().map(str::to_string);
+    let base_path = config.base_path.clone();
+    if args.print_config {
+        print_effective_config(&config, open_browser)?;
+    }
 
     // Write state file BEFORE starting server to eliminate race condition
     // By the time /health returns 200, clients can rely on this file existing
-    write_state(&ServerState {
-        pid: std::process::id(),
-        addr: addr_str.clone(),
-        token: token.clone(),
-    })?;
-
-    let handle = match start_server_with(ServerConfig {
-        host: host.clone(),
-        port,
-        token: token.clone(),
-        dist_dir,
-        history_limit,
-    })
-    .await
-    {
+    write_state(
+        state_path,
+        &ServerState {
+            pid: std::process::id(),
+            addr: format!("{}:{}", config.host, config.port),
+            token: token.clone(),
+            admin_token: config.admin_token.clone(),
+            base_path: base_path.clone(),
+            admin_addr: config.admin_bind.map(|addr| addr.to_string()),
+        },
+    )?;
+
+    let handle = match start_server_with(config.clone()).await {
         Ok(h) => h,
         Err(e) => {
             // Server failed to start, clean up state file
-            remove_state();
+            remove_state(state_path);
             return Err(e);
         }
     };
 
     let addr: SocketAddr = handle.addr();
+    let base = format!("http://{}{}", addr, base_path.as_deref().unwrap_or(""));
 
     println!("RileyViewer server started");
-    println!("  Address: http://{}", addr);
+    println!("  Address: {}", base);
+    if let Some(admin) = handle.admin_addr() {
+        println!("  Admin: http://{}", admin);
+    }
     let url = if let Some(ref t) = token {
         println!("  Token: {}", t);
-        let url = format!("http://{}/?token={}", addr, t);
+        let url = format!("{}/?token={}", base, t);
         println!("  URL: {}", url);
         url
     } else {
-        format!("http://{}/", addr)
+        format!("{}/", base)
     };
+    if let Some(admin_token) = handle.admin_token().filter(|t| Some(t) != token.as_ref()) {
+        println!("  Admin token: {}", admin_token);
+    }
+    if args.qr {
+        print_qr(&url);
+    }
 
     if open_browser {
         let url_clone = url.clone();
@@ -183,43 +649,228 @@ async fn serve(host: String, port: u16, token: Option<String>, dist_dir: Option<
         });
     }
 
+    let stdin = args.from_stdin.then(|| publish_stdin(handle.clone()));
+
     println!();
+    if args.from_stdin {
+        println!("Publishing plots from stdin.");
+    }
     println!("Press Ctrl+C to stop.");
 
-    tokio::signal::ctrl_c().await?;
+    wait_for_shutdown(&handle, args, stdin).await?;
     println!("\nShutting down...");
-    handle.shutdown().await?;
-    remove_state();
+    handle.shutdown().await.into_result()?;
+    remove_state(state_path);
     Ok(())
 }
 
-fn status() -> Result<()> {
-    match read_state() {
-        Some(state) => {
-            if check_server_running(&state.addr) {
+/// Block until Ctrl+C, reloading the config file on each SIGHUP.
+#[cfg(unix)]
+async fn wait_for_shutdown(
+    handle: &ServerHandle,
+    args: &ServeArgs,
+    stdin: Option<oneshot::Receiver<()>>,
+) -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = signal(SignalKind::hangup()).context("failed to install SIGHUP handler")?;
+    let stdin_closed = stdin_closed(stdin);
+    tokio::pin!(stdin_closed);
+    loop {
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => return Ok(result?),
+            _ = hangup.recv() => reload_config(handle, args).await,
+            _ = wait_for_viewers_gone(handle), if args.once => return Ok(()),
+            _ = &mut stdin_closed, if args.exit_on_eof => return Ok(()),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_shutdown(
+    handle: &ServerHandle,
+    args: &ServeArgs,
+    stdin: Option<oneshot::Receiver<()>>,
+) -> Result<()> {
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => Ok(result?),
+        _ = wait_for_viewers_gone(handle), if args.once => Ok(()),
+        _ = stdin_closed(stdin), if args.exit_on_eof => Ok(()),
+    }
+}
+
+/// Plots piped to `--from-stdin`: a whole message, or just its content.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StdinPlot {
+    Message(Box<PlotMessage>),
+    Content(PlotContent),
+}
+
+/// Publish every line of stdin from a background thread, skipping lines
+/// that don't parse. The receiver resolves at EOF.
+///
+/// A plain thread rather than `spawn_blocking`, since the runtime waits for
+/// blocking tasks on shutdown and a read from an idle stdin never returns.
+fn publish_stdin(handle: ServerHandle) -> oneshot::Receiver<()> {
+    let (eof_tx, eof_rx) = oneshot::channel();
+    let runtime = tokio::runtime::Handle::current();
+    std::thread::spawn(move || {
+        for (n, line) in std::io::stdin().lock().lines().enumerate() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    eprintln!("Failed to read stdin: {}", e);
+                    break;
+                }
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let msg = match serde_json::from_str(&line) {
+                Ok(StdinPlot::Message(msg)) => *msg,
+                Ok(StdinPlot::Content(content)) => PlotMessage::new(content),
+                Err(e) => {
+                    eprintln!("Skipping stdin line {}: {}", n + 1, e);
+                    continue;
+                }
+            };
+            if let Err(e) = runtime.block_on(handle.publish(msg)) {
+                eprintln!("Failed to publish stdin line {}: {:#}", n + 1, e);
+            }
+        }
+        println!("\nStdin closed");
+        let _ = eof_tx.send(());
+    });
+    eof_rx
+}
+
+/// Resolves when `--from-stdin` hits EOF; never without it.
+async fn stdin_closed(stdin: Option<oneshot::Receiver<()>>) {
+    match stdin {
+        Some(eof) => {
+            let _ = eof.await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// How long `--once` waits for a viewer to come back, e.g. after a page reload.
+const ONCE_GRACE: std::time::Duration = std::time::Duration::from_secs(3);
+
+/// Resolve once every viewer has disconnected and none reconnected within
+/// [`ONCE_GRACE`].
+async fn wait_for_viewers_gone(handle: &ServerHandle) {
+    loop {
+        handle.wait_for_clients_gone().await;
+        tokio::time::sleep(ONCE_GRACE).await;
+        if handle.client_count() == 0 {
+            println!("\nLast viewer disconnected");
+            return;
+        }
+    }
+}
+
+/// Re-read the config file and apply whatever can change without a restart.
+///
+/// Flags still take precedence, so only settings left to the file can change.
+/// A file with any problem is ignored whole rather than half applied.
+#[cfg_attr(not(unix), allow(dead_code))]
+async fn reload_config(handle: &ServerHandle, args: &ServeArgs) {
+    let path = rv_config::config_file_path();
+    println!("Reloading config from {}", path.display());
+    let file_config = match load_config_strict() {
+        Ok(config) => config,
+        Err(problems) => {
+            print_config_problems(&path, &problems);
+            eprintln!("  Config not reloaded");
+            return;
+        }
+    };
+    let reloaded = args.server_config(&file_config);
+    let changes = handle.apply_runtime_config(&reloaded).await;
+    let values = serde_json::to_value(&reloaded).unwrap_or_default();
+    for name in changes.applied {
+        println!("  {name} = {}", values[name]);
+    }
+    for name in changes.restart_required {
+        eprintln!("  {name} changed but requires a restart to take effect");
+    }
+}
+
+fn status(state_path: &Path, format: StatusFormat) -> Result<()> {
+    let state = read_state(state_path);
+    let stale = state.as_ref().is_some_and(|s| !wait_healthy(&s.base_url()));
+    if stale {
+        remove_state(state_path);
+    }
+    let running = state.filter(|_| !stale);
+    match format {
+        StatusFormat::Plain => match running {
+            Some(state) => {
                 println!("Server running");
                 println!("  PID: {}", state.pid);
-                println!("  Address: http://{}", state.addr);
+                println!("  Address: {}", state.base_url());
                 if let Some(ref t) = state.token {
                     println!("  Token: {}", t);
-                    println!("  URL: http://{}/?token={}", state.addr, t);
+                    println!("  URL: {}", state.viewer_url());
                 }
-            } else {
-                println!("Server not running (stale state file)");
-                remove_state();
+            }
+            None if stale => println!("Server not running (stale state file)"),
+            None => println!("Server not running"),
+        },
+        StatusFormat::Env | StatusFormat::Fish | StatusFormat::Powershell => {
+            if let Some(state) = running {
+                print_env(&state, format);
             }
         }
-        None => {
-            println!("Server not running");
+        StatusFormat::Json => print_status_json(running.as_ref())?,
+    }
+    Ok(())
+}
+
+/// Exports for the running server in `format`'s shell syntax, single-quoted
+/// so the shell leaves the URL's `?` alone. Nothing is printed when no
+/// server is running.
+fn print_env(state: &ServerState, format: StatusFormat) {
+    let vars = [
+        ("RV_ADDR", Some(state.base_url())),
+        ("RV_TOKEN", state.token.clone()),
+        ("RV_URL", Some(state.viewer_url())),
+    ];
+    for (name, value) in vars {
+        let Some(value) = value else { continue };
+        match format {
+            StatusFormat::Fish => {
+                let value = value.replace('\\', "\\\\").replace('\'', "\\'");
+                println!("set -gx {name} '{value}'");
+            }
+            StatusFormat::Powershell => println!("$env:{name} = '{}'", value.replace('\'', "''")),
+            _ => println!("export {name}='{}'", value.replace('\'', "'\\''")),
         }
     }
+}
+
+fn print_status_json(state: Option<&ServerState>) -> Result<()> {
+    let status = match state {
+        Some(state) => serde_json::json!({
+            "running": true,
+            "pid": state.pid,
+            "addr": state.base_url(),
+            "token": state.token,
+            "admin_token": state.admin_token,
+            "url": state.viewer_url(),
+        }),
+        None => serde_json::json!({ "running": false }),
+    };
+    println!("{}", serde_json::to_string_pretty(&status)?);
     Ok(())
 }
 
-fn stop() -> Result<()> {
-    match read_state() {
+fn stop(state_path: &Path) -> Result<()> {
+    match read_state(state_path) {
         Some(state) => {
-            if check_server_running(&state.addr) {
+            if wait_healthy(&state.base_url()) {
                 // Send kill signal to the process
                 #[cfg(unix)]
                 {
@@ -230,8 +881,8 @@ fn stop() -> Result<()> {
                         println!("Sent stop signal to server (PID {})", state.pid);
                         // Wait a moment and verify
                         std::thread::sleep(std::time::Duration::from_millis(500));
-                        if !check_server_running(&state.addr) {
-                            remove_state();
+                        if !check_server_running(&state.base_url()) {
+                            remove_state(state_path);
                             println!("Server stopped");
                         } else {
                             println!("Server still running, may need manual kill");
@@ -246,7 +897,7 @@ fn stop() -> Result<()> {
                 }
             } else {
                 println!("Server not running");
-                remove_state();
+                remove_state(state_path);
             }
         }
         None => {
@@ -256,22 +907,18 @@ fn stop() -> Result<()> {
     Ok(())
 }
 
-fn open() -> Result<()> {
-    match read_state() {
+fn open(state_path: &Path) -> Result<()> {
+    match read_state(state_path) {
         Some(state) => {
-            if check_server_running(&state.addr) {
-                let url = if let Some(ref t) = state.token {
-                    format!("http://{}/?token={}", state.addr, t)
-                } else {
-                    format!("http://{}/", state.addr)
-                };
+            if wait_healthy(&state.base_url()) {
+                let url = state.viewer_url();
                 println!("Opening {}", url);
                 if let Err(e) = webbrowser::open(&url) {
                     eprintln!("Failed to open browser: {}", e);
                 }
             } else {
                 println!("Server not running");
-                remove_state();
+                remove_state(state_path);
             }
         }
         None => {
@@ -280,3 +927,238 @@ fn open() -> Result<()> {
     }
     Ok(())
 }
+
+fn url(state_path: &Path, copy: bool) -> Result<()> {
+    let Some(state) = read_state(state_path).filter(|s| wait_healthy(&s.base_url())) else {
+        println!("No server running. Start one with: rileyviewer serve");
+        return Ok(());
+    };
+    let url = state.viewer_url();
+    println!("{}", url);
+    if copy {
+        match copy_to_clipboard(&url) {
+            Ok(tool) => eprintln!("Copied to clipboard (via {})", tool),
+            Err(e) => eprintln!("Couldn't copy to clipboard: {:#}", e),
+        }
+    }
+    Ok(())
+}
+
+/// Print `url` as a QR code, two modules per character cell, or a note
+/// saying why not when it can't be shown.
+fn print_qr(url: &str) {
+    use qrcode::{render::unicode::Dense1x2, EcLevel, QrCode};
+
+    let code = match QrCode::with_error_correction_level(url, EcLevel::L) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Couldn't encode the URL as a QR code: {}", e);
+            return;
+        }
+    };
+    let rendered = code.render::<Dense1x2>().build();
+    let width = rendered
+        .lines()
+        .next()
+        .map_or(0, |line| line.chars().count());
+    match terminal_width() {
+        Some(cols) if cols < width => {
+            eprintln!(
+                "Terminal too narrow for the QR code ({} columns, need {})",
+                cols, width
+            );
+        }
+        _ => {
+            println!();
+            println!("{}", rendered);
+        }
+    }
+}
+
+/// Columns available on stdout, from `$COLUMNS` or the tty itself.
+fn terminal_width() -> Option<usize> {
+    if let Some(cols) = std::env::var("COLUMNS").ok().and_then(|c| c.parse().ok()) {
+        return Some(cols);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::fd::AsRawFd;
+
+        nix::ioctl_read_bad!(window_size, nix::libc::TIOCGWINSZ, nix::libc::winsize);
+        let mut size: nix::libc::winsize = unsafe { std::mem::zeroed() };
+        // SAFETY: TIOCGWINSZ only writes a winsize into the pointer we pass
+        let ok = unsafe { window_size(std::io::stdout().as_raw_fd(), &mut size) }.is_ok();
+        if ok && size.ws_col > 0 {
+            return Some(size.ws_col.into());
+        }
+    }
+    None
+}
+
+/// Pipe `text` into the platform's clipboard tool, returning the one used.
+fn copy_to_clipboard(text: &str) -> Result<&'static str> {
+    use std::process::{Command, Stdio};
+
+    let candidates: &[(&str, &[&str])] = if cfg!(target_os = "macos") {
+        &[("pbcopy", &[])]
+    } else if cfg!(windows) {
+        &[("clip", &[])]
+    } else {
+        &[
+            ("wl-copy", &[]),
+            ("xclip", &["-selection", "clipboard"]),
+            ("xsel", &["--clipboard", "--input"]),
+        ]
+    };
+    for &(tool, args) in candidates {
+        let Ok(mut child) = Command::new(tool)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            continue;
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        if child.wait()?.success() {
+            return Ok(tool);
+        }
+    }
+    anyhow::bail!("no clipboard available (headless session?)")
+}
+
+/// Check a config file (the default one unless given `path`), exiting 1 on any problem.
+fn validate_config(path: Option<PathBuf>) {
+    let path = path.unwrap_or_else(rv_config::config_file_path);
+    match rv_config::validate(&path) {
+        Ok(()) => println!("{} is valid", path.display()),
+        Err(problems) => {
+            print_config_problems(&path, &problems);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Delete every plot in the running server's history.
+fn clear(state_path: &Path) -> Result<()> {
+    let Some(state) = read_state(state_path).filter(|s| wait_healthy(&s.base_url())) else {
+        println!("No server running. Start one with: rileyviewer serve");
+        return Ok(());
+    };
+    let token = state.admin_token.as_deref().or(state.token.as_deref());
+    let cleared = rv_client::clear(&state.admin_url(), token)?;
+    println!(
+        "Cleared {cleared} {}",
+        if cleared == 1 { "plot" } else { "plots" }
+    );
+    Ok(())
+}
+
+/// Publish synthetic plots to the running server and report what it sustained.
+fn bench(state_path: &Path, args: &bench::BenchArgs) -> Result<()> {
+    let Some(state) = read_state(state_path).filter(|s| wait_healthy(&s.base_url())) else {
+        println!("No server running. Start one with: rileyviewer serve");
+        return Ok(());
+    };
+    bench::run(&state.base_url(), state.token.as_deref(), args)
+}
+
+/// Follow the server's WebSocket like `tail -f`: history first, then live plots.
+fn tail(state_path: &Path, preview: Option<usize>) -> Result<()> {
+    let Some(state) = read_state(state_path) else {
+        println!("No server running. Start one with: rileyviewer serve");
+        return Ok(());
+    };
+    // Summaries are all we print unless a preview needs the payload
+    let mut url = format!(
+        "ws://{}{}/ws?meta_only={}",
+        state.addr,
+        state.base_path.as_deref().unwrap_or(""),
+        preview.is_none()
+    );
+    if let Some(ref t) = state.token {
+        url.push_str(&format!("&token={}", t));
+    }
+    let (mut socket, _) =
+        tungstenite::connect(&url).with_context(|| format!("failed to connect to {}", url))?;
+    loop {
+        let text = match socket.read() {
+            Ok(tungstenite::Message::Text(text)) => text,
+            Ok(tungstenite::Message::Close(_)) | Err(tungstenite::Error::ConnectionClosed) => break,
+            Ok(_) => continue,
+            Err(e) => return Err(e).context("WebSocket connection failed"),
+        };
+        match serde_json::from_str::<serde_json::Value>(&text) {
+            Ok(frame) => println!("{}", summarize_frame(&frame, preview)),
+            Err(e) => eprintln!("Ignoring unparseable frame: {}", e),
+        }
+    }
+    println!("Server closed the connection");
+    Ok(())
+}
+
+/// One line per frame: `time  id  type  size`, plus an optional text preview.
+fn summarize_frame(frame: &serde_json::Value, preview: Option<usize>) -> String {
+    if let Some(kind) = frame["kind"].as_str() {
+        return format!("-- {}: {}", kind, frame);
+    }
+    let time = frame["timestamp"]
+        .as_u64()
+        .map(format_utc_time)
+        .unwrap_or_default();
+    let id = frame["id"].as_str().unwrap_or("?");
+    // Full plots nest `{type, data}` under `content`; meta_only frames are flat
+    let (content_type, data) = match frame.get("content") {
+        Some(content) => (
+            content["type"].as_str().unwrap_or("?"),
+            content["data"]
+                .as_str()
+                .or_else(|| content["data"]["data"].as_str()),
+        ),
+        None => (frame["type"].as_str().unwrap_or("?"), None),
+    };
+    let byte_len = frame["byte_len"]
+        .as_u64()
+        .or(data.map(|d| d.len() as u64))
+        .unwrap_or(0);
+    let mut line = format!(
+        "{}  {}  {:<6}  {}",
+        time,
+        id,
+        content_type,
+        format_bytes(byte_len)
+    );
+    let is_raster = matches!(content_type, "Png" | "Jpeg" | "Webp" | "Avif" | "Gif");
+    if let (Some(n), Some(data), false) = (preview, data, is_raster) {
+        let text: String = data
+            .chars()
+            .take(n)
+            .map(|c| if c.is_whitespace() { ' ' } else { c })
+            .collect();
+        line.push_str(&format!("  {}", text));
+    }
+    line
+}
+
+/// `HH:MM:SS.mmm` in UTC for a Unix timestamp in milliseconds.
+fn format_utc_time(millis: u64) -> String {
+    let secs = millis / 1000;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}Z",
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60,
+        millis % 1000
+    )
+}
+
+fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1 << 20 => format!("{:.1} MiB", b as f64 / (1 << 20) as f64),
+        b if b >= 1 << 10 => format!("{:.1} KiB", b as f64 / (1 << 10) as f64),
+        b => format!("{} B", b),
+    }
+}
@@ -0,0 +1,143 @@
+//! Publishing plots to an already-running rileyviewer over HTTP.
+//!
+//! A thin wrapper over the server's `/api/publish` (and `DELETE /api/history`
+//! for [`clear`]), for Rust programs that want to push to a viewer started
+//! elsewhere instead of embedding `rv_server`. Enable the `async` feature for
+//! [`publish_async`].
+
+use std::time::Duration;
+
+use anyhow::Context;
+pub use rv_core::PlotContent;
+use serde::{Deserialize, Serialize};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Serialize)]
+struct PublishRequest<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<&'a str>,
+    content: &'a PlotContent,
+}
+
+#[derive(Deserialize)]
+struct PublishResponse {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct ClearResponse {
+    cleared: usize,
+}
+
+/// Publish `content` to the server at `addr` and return the new plot's id.
+///
+/// `addr` is the server's root URL, e.g. `http://127.0.0.1:8080` or with a
+/// base path; a bare `host:port` is taken as plain HTTP.
+pub fn publish(addr: &str, token: Option<&str>, content: PlotContent) -> anyhow::Result<String> {
+    let url = api_url(addr, "publish");
+    let body = serde_json::to_string(&PublishRequest {
+        token,
+        content: &content,
+    })?;
+    let request = ureq::post(&url)
+        .timeout(REQUEST_TIMEOUT)
+        .set("Content-Type", "application/json");
+    let response: PublishResponse = read_response(&url, request.send_string(&body))?;
+    Ok(response.id)
+}
+
+/// Remove every plot, pinned ones included, from the server at `addr` and
+/// return how many there were.
+///
+/// This is an admin route: `token` is the server's admin token, and with
+/// `admin_bind` set, `addr` must be the admin listener rather than the viewer's.
+pub fn clear(addr: &str, token: Option<&str>) -> anyhow::Result<usize> {
+    let url = api_url(addr, "history");
+    let mut request = ureq::delete(&url).timeout(REQUEST_TIMEOUT);
+    if let Some(token) = token {
+        request = request.query("token", token);
+    }
+    let response: ClearResponse = read_response(&url, request.call())?;
+    Ok(response.cleared)
+}
+
+fn read_response<T: serde::de::DeserializeOwned>(
+    url: &str,
+    response: Result<ureq::Response, ureq::Error>,
+) -> anyhow::Result<T> {
+    let response = match response {
+        Ok(response) => response,
+        Err(ureq::Error::Status(code, response)) => {
+            let reason = response.into_string().unwrap_or_default();
+            anyhow::bail!("{url} returned HTTP {code}: {}", reason.trim());
+        }
+        Err(e) => return Err(e).with_context(|| format!("failed to reach {url}")),
+    };
+    serde_json::from_str(&response.into_string()?).context("unexpected response from server")
+}
+
+/// [`publish`] on Tokio's blocking pool, so it doesn't stall the runtime.
+#[cfg(feature = "async")]
+pub async fn publish_async(
+    addr: &str,
+    token: Option<&str>,
+    content: PlotContent,
+) -> anyhow::Result<String> {
+    let (addr, token) = (addr.to_string(), token.map(str::to_string));
+    tokio::task::spawn_blocking(move || publish(&addr, token.as_deref(), content))
+        .await
+        .context("publish task failed")?
+}
+
+fn api_url(addr: &str, endpoint: &str) -> String {
+    let addr = addr.trim_end_matches('/');
+    if addr.contains("://") {
+        format!("{addr}/api/{endpoint}")
+    } else {
+        format!("http://{addr}/api/{endpoint}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_publish_reaches_running_server() {
+        let server = rv_server::start_server_with(rv_server::ServerConfig {
+            port: 0,
+            token: rv_server::TokenConfig::Fixed("secret".into()),
+            admin_token: Some("admin-secret".into()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let addr = server.addr().to_string();
+
+        let content = PlotContent::Svg("<svg/>".into());
+        let id = tokio::task::spawn_blocking({
+            let addr = addr.clone();
+            move || publish(&addr, Some("secret"), content)
+        })
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(!id.is_empty());
+
+        let rejected = tokio::task::spawn_blocking({
+            let addr = addr.clone();
+            move || publish(&addr, Some("wrong"), PlotContent::Svg("<svg/>".into()))
+        })
+        .await
+        .unwrap();
+        let err = rejected.unwrap_err().to_string();
+        assert!(err.contains("HTTP 401"), "{err}");
+
+        let cleared = tokio::task::spawn_blocking(move || clear(&addr, Some("admin-secret")))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(cleared, 1);
+    }
+}
@@ -4,13 +4,22 @@
 //! and can be overridden by CLI flags.
 
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use tracing::debug;
 
+mod validate;
+
+pub use validate::{validate, ConfigProblem};
+
 /// Default values
 pub const DEFAULT_HOST: &str = "127.0.0.1";
 pub const DEFAULT_PORT: u16 = 7878;
 pub const DEFAULT_HISTORY_LIMIT: usize = 200;
+pub const DEFAULT_SEND_TIMEOUT_SECS: u64 = 30;
 
 /// The main configuration structure.
 ///
@@ -20,6 +29,7 @@ pub const DEFAULT_HISTORY_LIMIT: usize = 200;
 #[serde(default)]
 pub struct Config {
     pub server: ServerConfig,
+    pub logging: LoggingConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,10 +39,76 @@ pub struct ServerConfig {
     pub host: String,
     /// Port to bind the server to
     pub port: u16,
-    /// Maximum number of plots to keep in history
+    /// Loopback address for a separate listener serving the admin routes
+    pub admin_bind: Option<SocketAddr>,
+    /// Token the admin routes require instead of the publish token; generated
+    /// at startup if unset, unless they're on `admin_bind`
+    pub admin_token: Option<String>,
+    /// Maximum number of plots to keep in history; 0 keeps none (live only)
     pub history_limit: usize,
     /// Whether to open browser automatically on server start
     pub open_browser: bool,
+    /// Path prefix to serve under when behind a reverse proxy (e.g. `/viewer`)
+    pub base_path: Option<String>,
+    /// Directory of files that shadow the web UI's own (custom CSS, logo, ...)
+    pub overlay_dir: Option<String>,
+    /// Serve the bundled web UI; disable for API-only deployments with their own frontend
+    pub serve_spa: bool,
+    /// Seconds a WebSocket send may block before the client is dropped (0 disables)
+    pub send_timeout_secs: u64,
+    /// Reject publishes while no viewer is connected instead of storing them
+    pub require_client: bool,
+    /// Restart the HTTP server if it dies unexpectedly instead of staying down
+    pub auto_restart: bool,
+    /// Longest edge of generated PNG thumbnails; unset disables thumbnails
+    pub thumbnail_max_px: Option<u32>,
+    /// File to persist history to across restarts, gzipped if it ends in
+    /// `.gz` or zstd-compressed if `.zst`; unset keeps history in memory only
+    pub persist_path: Option<PathBuf>,
+    /// fsync each persisted write before the publish is answered, so a crash
+    /// or power loss can't lose acknowledged plots
+    pub persist_durable: bool,
+    /// Pretty-print JSON on the wire for debugging (larger payloads)
+    pub pretty_json: bool,
+    /// Recycle WebSocket connections after this many seconds; unset keeps them open
+    pub max_connection_secs: Option<u64>,
+    /// Reject published content types not in this list (e.g. `["Svg", "Plotly"]`)
+    pub allowed_content_types: Option<Vec<String>>,
+    /// Ignore publishes identical to the newest plot (for loops re-sending an unchanged figure)
+    pub dedup: bool,
+    /// Strip scripts and event handlers from published HTML; recommended when
+    /// publishers aren't fully trusted
+    pub sanitize_html: bool,
+    /// How to treat WebSocket clients too slow to keep up with the broadcast
+    pub slow_consumer: SlowConsumer,
+    /// What to do when a publish supplies an id already in history
+    pub duplicate_ids: DuplicateIds,
+    /// Which plot the history limit evicts: oldest published, or least recently viewed
+    pub eviction: Eviction,
+    /// Re-encode PNG plots into this format before storing them
+    pub transcode: Option<Transcode>,
+    /// Rendering suggestions for the web UI by content type (e.g. `Svg = "inline"`)
+    pub render_hints: HashMap<String, String>,
+    /// Headers added to every response (`[server.extra_headers]`), e.g. HSTS behind a TLS proxy
+    pub extra_headers: HashMap<String, String>,
+    /// Add CSP, `X-Frame-Options`, and similar hardening headers to every response
+    pub secure_headers: bool,
+    /// Believe `X-Forwarded-Proto` when building absolute links; only for a
+    /// reverse proxy in front of the server that sets it
+    pub trust_proxy: bool,
+    /// Move the web UI's `?token=` into a cookie and embed it in the served page
+    pub inject_token: bool,
+    /// HMAC key for signing plots so key holders can verify who published them
+    pub sign_key: Option<String>,
+    /// Mirror every published plot by POSTing its JSON to this URL
+    pub webhook_url: Option<String>,
+    /// Bearer token for webhook requests
+    pub webhook_token: Option<String>,
+    /// Forward every published plot to another rileyviewer (`[server.upstream]`)
+    pub upstream: Option<UpstreamConfig>,
+    /// Keep an audit trail of every published plot in daily files named
+    /// after this path, e.g. `audit.log.2026-10-14`
+    pub audit_log: Option<PathBuf>,
 }
 
 impl Default for ServerConfig {
@@ -40,8 +116,231 @@ impl Default for ServerConfig {
         Self {
             host: DEFAULT_HOST.to_string(),
             port: DEFAULT_PORT,
+            admin_bind: None,
+            admin_token: None,
             history_limit: DEFAULT_HISTORY_LIMIT,
             open_browser: true,
+            base_path: None,
+            overlay_dir: None,
+            serve_spa: true,
+            send_timeout_secs: DEFAULT_SEND_TIMEOUT_SECS,
+            require_client: false,
+            auto_restart: false,
+            thumbnail_max_px: None,
+            persist_path: None,
+            persist_durable: false,
+            pretty_json: false,
+            max_connection_secs: None,
+            allowed_content_types: None,
+            dedup: false,
+            sanitize_html: false,
+            slow_consumer: SlowConsumer::default(),
+            duplicate_ids: DuplicateIds::default(),
+            eviction: Eviction::default(),
+            transcode: None,
+            render_hints: HashMap::new(),
+            extra_headers: HashMap::new(),
+            secure_headers: false,
+            trust_proxy: false,
+            inject_token: false,
+            sign_key: None,
+            webhook_url: None,
+            webhook_token: None,
+            upstream: None,
+            audit_log: None,
+        }
+    }
+}
+
+/// What to do with a WebSocket client that falls behind the live broadcast.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SlowConsumer {
+    /// Skip the plots it missed and keep streaming
+    #[default]
+    DropOldest,
+    /// Close the connection so the client reconnects and replays history
+    Disconnect,
+}
+
+impl fmt::Display for SlowConsumer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SlowConsumer::DropOldest => "drop_oldest",
+            SlowConsumer::Disconnect => "disconnect",
+        })
+    }
+}
+
+impl FromStr for SlowConsumer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('-', "_").as_str() {
+            "drop_oldest" => Ok(SlowConsumer::DropOldest),
+            "disconnect" => Ok(SlowConsumer::Disconnect),
+            other => Err(format!(
+                "unknown slow consumer policy '{other}' (expected drop_oldest or disconnect)"
+            )),
+        }
+    }
+}
+
+/// A rileyviewer server that plots are mirrored to, ids and timestamps intact.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpstreamConfig {
+    /// Base URL of the upstream server, e.g. `http://hub:8080` (plus any base path)
+    pub url: String,
+    /// The upstream's publish token; never printed with the effective config
+    #[serde(default, skip_serializing)]
+    pub token: Option<String>,
+}
+
+/// What to do with a publish whose client-supplied id is already in history.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateIds {
+    /// Refuse the new plot with 409 Conflict
+    #[default]
+    Reject,
+    /// Drop the existing plot and store the new one as the newest
+    Replace,
+}
+
+impl fmt::Display for DuplicateIds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DuplicateIds::Reject => "reject",
+            DuplicateIds::Replace => "replace",
+        })
+    }
+}
+
+impl FromStr for DuplicateIds {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "reject" => Ok(DuplicateIds::Reject),
+            "replace" => Ok(DuplicateIds::Replace),
+            other => Err(format!(
+                "unknown duplicate id policy '{other}' (expected reject or replace)"
+            )),
+        }
+    }
+}
+
+/// Which unpinned plot goes when history is over its limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Eviction {
+    /// The oldest published
+    #[default]
+    Fifo,
+    /// The one least recently fetched from `/api/plots/:id`, counting
+    /// publishing as a view so new plots aren't evicted first
+    LruViewed,
+}
+
+impl fmt::Display for Eviction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Eviction::Fifo => "fifo",
+            Eviction::LruViewed => "lru_viewed",
+        })
+    }
+}
+
+impl FromStr for Eviction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('-', "_").as_str() {
+            "fifo" => Ok(Eviction::Fifo),
+            "lru_viewed" => Ok(Eviction::LruViewed),
+            other => Err(format!(
+                "unknown eviction policy '{other}' (expected fifo or lru_viewed)"
+            )),
+        }
+    }
+}
+
+/// Image format that PNG plots are transcoded into on publish.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transcode {
+    /// Lossless WebP, typically a quarter smaller than PNG
+    WebP,
+    /// Lossy AVIF: usually smaller still, but slower to encode and not
+    /// pixel-exact
+    Avif,
+}
+
+impl fmt::Display for Transcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Transcode::WebP => "webp",
+            Transcode::Avif => "avif",
+        })
+    }
+}
+
+impl FromStr for Transcode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "webp" => Ok(Transcode::WebP),
+            "avif" => Ok(Transcode::Avif),
+            other => Err(format!(
+                "unknown transcode format '{other}' (expected webp or avif)"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Log filter directive (e.g. `info` or `rv_server=debug`).
+    ///
+    /// When unset, `RUST_LOG` is honored; with neither set, logging stays off.
+    pub level: Option<String>,
+    /// Output format for log lines
+    pub format: LogFormat,
+}
+
+/// Log output format.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Compact,
+    Pretty,
+    Json,
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LogFormat::Compact => "compact",
+            LogFormat::Pretty => "pretty",
+            LogFormat::Json => "json",
+        })
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "compact" => Ok(LogFormat::Compact),
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!(
+                "unknown log format '{other}' (expected compact, pretty, or json)"
+            )),
         }
     }
 }
@@ -80,6 +379,12 @@ impl Config {
         }
     }
 
+    /// Load `path`, failing with every problem [`validate`] finds, such as
+    /// unknown (likely misspelled) keys, instead of falling back to defaults.
+    pub fn load_strict(path: &Path) -> Result<Self, Vec<ConfigProblem>> {
+        validate::parse(path)
+    }
+
     /// Write a default config file to the default location.
     ///
     /// Creates parent directories if needed. Returns the path written to.
@@ -148,6 +453,22 @@ mod tests {
         assert_eq!(config.server.port, DEFAULT_PORT);
         assert_eq!(config.server.history_limit, DEFAULT_HISTORY_LIMIT);
         assert!(config.server.open_browser);
+        assert!(config.logging.level.is_none());
+        assert_eq!(config.logging.format, LogFormat::Compact);
+    }
+
+    #[test]
+    fn test_parse_logging_config() {
+        let toml = r#"
+[logging]
+level = "debug"
+format = "json"
+"#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.logging.level.as_deref(), Some("debug"));
+        assert_eq!(config.logging.format, LogFormat::Json);
+        assert_eq!("Pretty".parse::<LogFormat>(), Ok(LogFormat::Pretty));
+        assert!("verbose".parse::<LogFormat>().is_err());
     }
 
     #[test]
@@ -163,6 +484,21 @@ history_limit = 500
         assert_eq!(config.server.port, DEFAULT_PORT);
     }
 
+    #[test]
+    fn test_load_strict_rejects_misspelled_key() {
+        let path = std::env::temp_dir().join(format!("rv_strict_{}.toml", std::process::id()));
+        std::fs::write(&path, "[server]\nhistory_limit = 5\n").unwrap();
+        assert_eq!(Config::load_strict(&path).unwrap().server.history_limit, 5);
+
+        std::fs::write(&path, "[server]\nhistroy_limit = 5\n").unwrap();
+        let problems = Config::load_strict(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            problems[0].to_string(),
+            "server.histroy_limit: unknown key, did you mean `history_limit`?"
+        );
+    }
+
     #[test]
     fn test_serialize_config() {
         let config = Config::default();
@@ -0,0 +1,316 @@
+//! Checking a config file for every problem at once, for `rileyviewer config
+//! validate` and CI.
+//!
+//! [`Config::load`](crate::Config::load) falls back to defaults on the first
+//! error. Here the file is read as a plain TOML table instead: keys are
+//! checked against the fields the config structs accept, each key is
+//! deserialized on its own so one bad value doesn't hide the next, and the
+//! values that parse are then checked for sense, alongside any errors found.
+
+use std::{fmt, path::Path};
+
+use serde::{
+    de::{self, DeserializeOwned, Visitor},
+    Deserializer,
+};
+
+use crate::{Config, LoggingConfig, ServerConfig, UpstreamConfig};
+
+/// History limits above this keep more plots in memory than is likely intended.
+const MAX_HISTORY_LIMIT: usize = 1_000_000;
+
+/// One thing wrong with a config file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigProblem {
+    /// Dotted key path, e.g. `server.port`; empty for the file as a whole
+    pub field: String,
+    pub message: String,
+}
+
+impl ConfigProblem {
+    pub(crate) fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.field.as_str() {
+            "" => f.write_str(&self.message),
+            field => write!(f, "{field}: {}", self.message),
+        }
+    }
+}
+
+/// Check the config file at `path`, returning every problem found.
+pub fn validate(path: &Path) -> Result<(), Vec<ConfigProblem>> {
+    parse(path).map(|_| ())
+}
+
+/// Read `path` once and check it, returning the config it holds if nothing
+/// is wrong with it.
+pub(crate) fn parse(path: &Path) -> Result<Config, Vec<ConfigProblem>> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| vec![ConfigProblem::new("", format!("can't read {path:?}: {e}"))])?;
+    let table: toml::Table = toml::from_str(&contents)
+        .map_err(|e| vec![ConfigProblem::new("", e.message().to_string())])?;
+
+    let mut problems = Vec::new();
+    check_keys::<Config>(&table, "", &mut problems);
+    let server = check_section::<ServerConfig>(&table, "server", &mut problems);
+    let logging = check_section::<LoggingConfig>(&table, "logging", &mut problems);
+    let parsed = toml::Table::from_iter([
+        ("server".to_string(), server.into()),
+        ("logging".to_string(), logging.into()),
+    ]);
+    if let Some(toml::Value::Table(server)) = table.get("server") {
+        if let Some(toml::Value::Table(upstream)) = server.get("upstream") {
+            check_keys::<UpstreamConfig>(upstream, "server.upstream", &mut problems);
+        }
+    }
+    // Judge what did parse, so a typo elsewhere doesn't hide a bad path
+    let config = match toml::Value::Table(parsed).try_into::<Config>() {
+        Ok(config) => {
+            check_values(&config.server, &mut problems);
+            config
+        }
+        Err(e) => {
+            problems.push(ConfigProblem::new("", e.message().to_string()));
+            return Err(problems);
+        }
+    };
+    if problems.is_empty() {
+        Ok(config)
+    } else {
+        Err(problems)
+    }
+}
+
+/// Flag keys of `table` that `T` has no field for.
+fn check_keys<T: DeserializeOwned>(
+    table: &toml::Table,
+    prefix: &str,
+    problems: &mut Vec<ConfigProblem>,
+) {
+    let known = fields::<T>();
+    for key in table.keys() {
+        if !known.contains(&key.as_str()) {
+            let message = match closest(key, known) {
+                Some(suggestion) => format!("unknown key, did you mean `{suggestion}`?"),
+                None => "unknown key".to_string(),
+            };
+            problems.push(ConfigProblem::new(join(prefix, key), message));
+        }
+    }
+}
+
+/// Check the `[name]` table's keys, and each value on its own, returning
+/// the known keys whose values parse.
+fn check_section<T: DeserializeOwned>(
+    table: &toml::Table,
+    name: &str,
+    problems: &mut Vec<ConfigProblem>,
+) -> toml::Table {
+    let mut parsed = toml::Table::new();
+    let Some(section) = table.get(name) else {
+        return parsed;
+    };
+    let toml::Value::Table(section) = section else {
+        problems.push(ConfigProblem::new(name, "expected a table"));
+        return parsed;
+    };
+    check_keys::<T>(section, name, problems);
+    let known = fields::<T>();
+    for (key, value) in section
+        .iter()
+        .filter(|(key, _)| known.contains(&key.as_str()))
+    {
+        // Every other field takes its default, so any error is this value's
+        let single = toml::Table::from_iter([(key.clone(), value.clone())]);
+        match toml::Value::Table(single).try_into::<T>() {
+            Ok(_) => {
+                parsed.insert(key.clone(), value.clone());
+            }
+            Err(e) => problems.push(ConfigProblem::new(join(name, key), e.message().to_string())),
+        }
+    }
+    parsed
+}
+
+/// Values that parse but that the server would refuse or that are surely mistakes.
+fn check_values(server: &ServerConfig, problems: &mut Vec<ConfigProblem>) {
+    let mut problem = |field: &str, message: String| {
+        problems.push(ConfigProblem::new(format!("server.{field}"), message));
+    };
+    if server.history_limit > MAX_HISTORY_LIMIT {
+        problem(
+            "history_limit",
+            format!("{} is more than {MAX_HISTORY_LIMIT}", server.history_limit),
+        );
+    }
+    if let Some(addr) = server.admin_bind.filter(|addr| !addr.ip().is_loopback()) {
+        problem("admin_bind", format!("{addr} is not a loopback address"));
+    }
+    if let Some(dir) = server
+        .overlay_dir
+        .as_deref()
+        .filter(|dir| !Path::new(dir).is_dir())
+    {
+        problem("overlay_dir", format!("{dir:?} is not a directory"));
+    }
+    let files = [
+        ("persist_path", server.persist_path.as_deref()),
+        ("audit_log", server.audit_log.as_deref()),
+    ];
+    for (field, path) in files {
+        let Some(path) = path else {
+            continue;
+        };
+        let parent = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty());
+        if parent.is_some_and(|parent| !parent.is_dir()) {
+            problem(field, format!("directory of {path:?} does not exist"));
+        }
+    }
+    let urls = [
+        ("webhook_url", server.webhook_url.as_deref()),
+        (
+            "upstream.url",
+            server.upstream.as_ref().map(|u| u.url.as_str()),
+        ),
+    ];
+    for (field, url) in urls {
+        let http = |url: &&str| url.starts_with("http://") || url.starts_with("https://");
+        if let Some(url) = url.filter(|url| !http(url)) {
+            problem(field, format!("{url:?} is not an http(s) URL"));
+        }
+    }
+}
+
+/// The known key within a few edits of `key`, if any: close enough to be a
+/// typo, not so loose that every short key matches.
+fn closest<'a>(key: &str, known: &[&'a str]) -> Option<&'a str> {
+    let max_distance = (key.chars().count() / 3).max(1);
+    known
+        .iter()
+        .map(|candidate| (levenshtein(key, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+fn join(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{prefix}.{key}")
+    }
+}
+
+/// The field names `T`'s derived `Deserialize` accepts, read from the list
+/// it hands to `deserialize_struct`, so it can't drift from the struct.
+fn fields<T: DeserializeOwned>() -> &'static [&'static str] {
+    struct Probe<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de> Deserializer<'de> for Probe<'_> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("not a struct"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            Err(de::Error::custom("fields probed"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(Probe(&mut fields));
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_reports_every_problem() {
+        let path = std::env::temp_dir().join(format!("rv_validate_{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"
+colour = "blue"
+
+[server]
+port = 70000
+history_limit = -1
+hostname = "x"
+overlay_dir = "/nonexistent/theme"
+admin_bind = "0.0.0.0:9000"
+
+[logging]
+format = "xml"
+"#,
+        )
+        .unwrap();
+        let problems = validate(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        let fields: Vec<&str> = problems.iter().map(|p| p.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            [
+                "colour",
+                "server.hostname",
+                "server.history_limit",
+                "server.port",
+                "logging.format",
+                // Semantic checks still see the values that parsed
+                "server.admin_bind",
+                "server.overlay_dir",
+            ]
+        );
+
+        std::fs::write(&path, "[server]\nhistroy_limit = 5\nzzz = 1\n").unwrap();
+        let problems = validate(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        let messages: Vec<String> = problems.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "server.histroy_limit: unknown key, did you mean `history_limit`?",
+                "server.zzz: unknown key",
+            ]
+        );
+    }
+}
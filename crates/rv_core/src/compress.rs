@@ -0,0 +1,147 @@
+//! Compression for history files, chosen by file extension.
+//!
+//! A `.gz` file is gzip, `.zst` zstd, anything else plain text. Appends to a
+//! compressed file are written as separate gzip members or zstd frames, which
+//! concatenate into a valid stream, so a file never has to be recompressed to
+//! add a line. Shared by the server's persist file and anything else that
+//! writes history, so each can pick its codec on its own.
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use flate2::{read::MultiGzDecoder, write::GzEncoder, Compression};
+
+/// zstd's own default; higher levels cost far more time than they save space.
+const ZSTD_LEVEL: i32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Plain,
+    Gzip,
+    Zstd,
+}
+
+impl Codec {
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("gz") => Codec::Gzip,
+            Some(ext) if ext.eq_ignore_ascii_case("zst") => Codec::Zstd,
+            _ => Codec::Plain,
+        }
+    }
+
+    /// A reader yielding the decompressed contents of `file`.
+    pub fn reader(self, file: File) -> io::Result<Box<dyn BufRead>> {
+        Ok(match self {
+            Codec::Plain => Box::new(BufReader::new(file)),
+            Codec::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(BufReader::new(file)))),
+            Codec::Zstd => Box::new(BufReader::new(zstd::Decoder::new(file)?)),
+        })
+    }
+
+    /// A writer compressing everything written to it into `file`.
+    pub fn writer(self, file: File) -> io::Result<CompressedWriter> {
+        let file = BufWriter::new(file);
+        Ok(match self {
+            Codec::Plain => CompressedWriter::Plain(file),
+            Codec::Gzip => CompressedWriter::Gzip(GzEncoder::new(file, Compression::default())),
+            Codec::Zstd => CompressedWriter::Zstd(zstd::Encoder::new(file, ZSTD_LEVEL)?),
+        })
+    }
+
+    /// `bytes` as a self-contained chunk that can be appended to a file
+    /// in this format.
+    pub fn encode(self, bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+        match self {
+            Codec::Plain => Ok(bytes),
+            Codec::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&bytes)?;
+                encoder.finish()
+            }
+            Codec::Zstd => zstd::encode_all(bytes.as_slice(), ZSTD_LEVEL),
+        }
+    }
+}
+
+pub enum CompressedWriter {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl CompressedWriter {
+    /// Write any trailer and flush, handing back the file for syncing.
+    pub fn finish(self) -> io::Result<File> {
+        let buffered = match self {
+            CompressedWriter::Plain(file) => file,
+            CompressedWriter::Gzip(encoder) => encoder.finish()?,
+            CompressedWriter::Zstd(encoder) => encoder.finish()?,
+        };
+        buffered
+            .into_inner()
+            .map_err(io::IntoInnerError::into_error)
+    }
+}
+
+impl Write for CompressedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            CompressedWriter::Plain(file) => file.write(buf),
+            CompressedWriter::Gzip(encoder) => encoder.write(buf),
+            CompressedWriter::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            CompressedWriter::Plain(file) => file.flush(),
+            CompressedWriter::Gzip(encoder) => encoder.flush(),
+            CompressedWriter::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    #[test]
+    fn test_round_trip_each_codec() {
+        let dir = std::env::temp_dir().join(format!("rv_compress_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let text = "{\"id\":\"a\"}\n".repeat(200);
+        for (name, codec) in [
+            ("history.ndjson", Codec::Plain),
+            ("history.ndjson.gz", Codec::Gzip),
+            ("history.ndjson.zst", Codec::Zstd),
+        ] {
+            let path = dir.join(name);
+            assert_eq!(Codec::for_path(&path), codec);
+            let mut out = codec.writer(File::create(&path).unwrap()).unwrap();
+            out.write_all(text.as_bytes()).unwrap();
+            let mut file = out.finish().unwrap();
+            // An appended chunk continues the stream
+            file.write_all(&codec.encode(b"tail\n".to_vec()).unwrap())
+                .unwrap();
+
+            let mut read = String::new();
+            codec
+                .reader(File::open(&path).unwrap())
+                .unwrap()
+                .read_to_string(&mut read)
+                .unwrap();
+            assert_eq!(read, format!("{text}tail\n"), "{name}");
+            if codec != Codec::Plain {
+                let len = std::fs::metadata(&path).unwrap().len() as usize;
+                assert!(len < text.len() / 10, "{name}: {len} bytes");
+            }
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,31 +1,356 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
 use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg(feature = "compress")]
+pub mod compress;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(tag = "type", content = "data")]
 pub enum PlotContent {
-    Png(String),   // base64-encoded PNG
-    Svg(String),   // raw SVG
+    Png(String),    // base64-encoded PNG
+    Jpeg(String),   // base64-encoded JPEG
+    Webp(String),   // base64-encoded WebP
+    Avif(String),   // base64-encoded AVIF
+    Gif(String),    // base64-encoded GIF
+    Svg(String),    // raw SVG
     Plotly(String), // JSON payload
     Vega(String),  // JSON payload (Vega/Vega-Lite)
     Html(String),  // raw HTML fallback
+    /// JSON/string payload rendered by the frontend plugin named `renderer`
+    Custom {
+        renderer: String,
+        data: String,
+    },
+    /// Two plots rendered side by side, captioned `labels.0` and `labels.1`
+    Compare {
+        left: Box<PlotContent>,
+        right: Box<PlotContent>,
+        labels: (String, String),
+    },
+}
+
+impl PlotContent {
+    /// The variant name, matching the serialized `type` tag (e.g. `"Png"`).
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            PlotContent::Png(_) => "Png",
+            PlotContent::Jpeg(_) => "Jpeg",
+            PlotContent::Webp(_) => "Webp",
+            PlotContent::Avif(_) => "Avif",
+            PlotContent::Gif(_) => "Gif",
+            PlotContent::Svg(_) => "Svg",
+            PlotContent::Plotly(_) => "Plotly",
+            PlotContent::Vega(_) => "Vega",
+            PlotContent::Html(_) => "Html",
+            PlotContent::Custom { .. } => "Custom",
+            PlotContent::Compare { .. } => "Compare",
+        }
+    }
+
+    /// Build the variant named `type_name` around `data`; `renderer` is required
+    /// for (and only used by) `Custom`. `None` for an unknown type, and for
+    /// `Compare`, whose panes don't fit in one payload string.
+    pub fn from_type_name(type_name: &str, data: String, renderer: Option<String>) -> Option<Self> {
+        Some(match type_name {
+            "Png" => PlotContent::Png(data),
+            "Jpeg" => PlotContent::Jpeg(data),
+            "Webp" => PlotContent::Webp(data),
+            "Avif" => PlotContent::Avif(data),
+            "Gif" => PlotContent::Gif(data),
+            "Svg" => PlotContent::Svg(data),
+            "Plotly" => PlotContent::Plotly(data),
+            "Vega" => PlotContent::Vega(data),
+            "Html" => PlotContent::Html(data),
+            "Custom" => PlotContent::Custom {
+                renderer: renderer?,
+                data,
+            },
+            _ => return None,
+        })
+    }
+
+    /// Size of the payload string in bytes (the base64 length for images).
+    pub fn byte_len(&self) -> usize {
+        match self {
+            PlotContent::Png(data)
+            | PlotContent::Jpeg(data)
+            | PlotContent::Webp(data)
+            | PlotContent::Avif(data)
+            | PlotContent::Gif(data)
+            | PlotContent::Svg(data)
+            | PlotContent::Plotly(data)
+            | PlotContent::Vega(data)
+            | PlotContent::Html(data)
+            | PlotContent::Custom { data, .. } => data.len(),
+            PlotContent::Compare { left, right, .. } => left.byte_len() + right.byte_len(),
+        }
+    }
+
+    /// Why this content can't be shown, if it can't: a `Compare` pane is
+    /// empty or is itself a `Compare`.
+    pub fn validate(&self) -> Result<(), String> {
+        let PlotContent::Compare { left, right, .. } = self else {
+            return Ok(());
+        };
+        for (side, pane) in [("left", left), ("right", right)] {
+            if matches!(**pane, PlotContent::Compare { .. }) {
+                return Err(format!("Compare {side} pane can't be another Compare"));
+            }
+            if pane.byte_len() == 0 {
+                return Err(format!("Compare {side} pane is empty"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Every content type name this content uses: its own, plus its panes'.
+    pub fn type_names(&self) -> Vec<&'static str> {
+        match self {
+            PlotContent::Compare { left, right, .. } => {
+                let mut names = vec![self.type_name()];
+                names.extend(left.type_names());
+                names.extend(right.type_names());
+                names
+            }
+            _ => vec![self.type_name()],
+        }
+    }
+
+    /// Append the signed form of this content: its type name, then the
+    /// `Custom` renderer or the `Compare` labels and panes, then the payload.
+    fn extend_signing_payload(&self, payload: &mut Vec<u8>) {
+        push_line(payload, self.type_name());
+        match self {
+            PlotContent::Custom { renderer, data } => {
+                push_line(payload, renderer);
+                push_line(payload, data);
+            }
+            PlotContent::Compare {
+                left,
+                right,
+                labels,
+            } => {
+                push_line(payload, &labels.0);
+                push_line(payload, &labels.1);
+                left.extend_signing_payload(payload);
+                right.extend_signing_payload(payload);
+            }
+            PlotContent::Png(data)
+            | PlotContent::Jpeg(data)
+            | PlotContent::Webp(data)
+            | PlotContent::Avif(data)
+            | PlotContent::Gif(data)
+            | PlotContent::Svg(data)
+            | PlotContent::Plotly(data)
+            | PlotContent::Vega(data)
+            | PlotContent::Html(data) => push_line(payload, data),
+        }
+    }
+
+    /// MIME type and base64 payload for the raster image variants.
+    pub fn raster(&self) -> Option<(&'static str, &str)> {
+        match self {
+            PlotContent::Png(data) => Some(("image/png", data)),
+            PlotContent::Jpeg(data) => Some(("image/jpeg", data)),
+            PlotContent::Webp(data) => Some(("image/webp", data)),
+            PlotContent::Avif(data) => Some(("image/avif", data)),
+            PlotContent::Gif(data) => Some(("image/gif", data)),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlotMessage {
     pub id: String,
+    /// Per-server publish order, counting up from 1 with no gaps; a client
+    /// that sees it jump has missed plots. 0 until the server stores it.
+    #[serde(default)]
+    pub seq: u64,
+    /// Plot number for display ("plot #N"), counting up from 1 across the
+    /// server's history. Unlike `seq` it survives the plot being replaced
+    /// under the same id. 0 until the server stores it.
+    #[serde(default)]
+    pub index: u64,
     /// Unix timestamp in milliseconds (safe for JavaScript Number)
     pub timestamp: u64,
-    pub content: PlotContent,
+    /// Unix timestamp in nanoseconds, strictly increasing across plots made
+    /// by this process, for ordering plots published within one millisecond.
+    /// A publisher that supplies its own `timestamp` doesn't change this, so
+    /// the two can disagree. 0 on plots stored before it was added.
+    #[serde(default)]
+    pub timestamp_ns: u64,
+    /// Shared, so a copy of the message made to change its metadata leaves
+    /// the payload where it is
+    pub content: Arc<PlotContent>,
+    /// Intrinsic `(width, height)` in pixels of image content, when the
+    /// server could tell, so viewers can lay out space before decoding it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dimensions: Option<(u32, u32)>,
+    /// Unix timestamp in milliseconds after which the server drops this plot
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// Pinned plots are never evicted by the history limit
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// Higher sorts first in `/api/history?sort=priority`; storage and
+    /// broadcast stay in publish order
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub priority: i32,
+    /// Free-form metadata from the publisher (run id, git commit, ...),
+    /// stored and broadcast untouched
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub annotations: HashMap<String, String>,
+    /// Report the plot belongs to, e.g. every figure from one analysis run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_id: Option<String>,
+    /// Display name of `group_id`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_title: Option<String>,
+    /// Hex HMAC-SHA256 of [`signing_payload`](Self::signing_payload) when the
+    /// server has a signing key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sig: Option<String>,
 }
 
 impl PlotMessage {
     pub fn new(content: PlotContent) -> Self {
+        let timestamp_ns = next_nanos();
         Self {
             id: Uuid::new_v4().to_string(),
-            timestamp: (OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000) as u64,
-            content,
+            seq: 0,
+            index: 0,
+            timestamp: timestamp_ns / 1_000_000,
+            timestamp_ns,
+            content: Arc::new(content),
+            dimensions: None,
+            expires_at: None,
+            pinned: false,
+            priority: 0,
+            annotations: HashMap::new(),
+            group_id: None,
+            group_title: None,
+            sig: None,
         }
     }
+
+    /// The bytes `sig` covers: `id`, the content type name, and the payload,
+    /// each followed by `\n`. `Custom` content puts its renderer before the
+    /// payload; `Compare` puts its two labels, then each pane signed the same
+    /// way (type name, renderer if `Custom`, payload).
+    pub fn signing_payload(&self) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(self.id.len() + self.content.byte_len() + 32);
+        push_line(&mut payload, &self.id);
+        self.content.extend_signing_payload(&mut payload);
+        payload
+    }
+
+    pub fn is_expired(&self, now_ms: u64) -> bool {
+        self.expires_at.is_some_and(|at| at <= now_ms)
+    }
+}
+
+fn is_zero(n: &i32) -> bool {
+    *n == 0
+}
+
+fn push_line(payload: &mut Vec<u8>, part: &str) {
+    payload.extend_from_slice(part.as_bytes());
+    payload.push(b'\n');
+}
+
+/// Current Unix time in milliseconds, the unit of every timestamp on the wire.
+pub fn now_millis() -> u64 {
+    (OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000) as u64
+}
+
+/// Current Unix time in nanoseconds, bumped past the last value handed out
+/// so two calls never tie even on a coarse clock.
+pub fn next_nanos() -> u64 {
+    static LAST: AtomicU64 = AtomicU64::new(0);
+    let now = OffsetDateTime::now_utc().unix_timestamp_nanos() as u64;
+    let prev = LAST
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |last| {
+            Some(now.max(last + 1))
+        })
+        .unwrap_or_else(|last| last);
+    now.max(prev + 1)
+}
+
+/// A title and description for the viewer as a whole, e.g. which run or
+/// experiment the plots belong to.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl SessionInfo {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.description.is_none()
+    }
+}
+
+/// Frames sent by WebSocket clients to the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ClientFrame {
+    /// Acknowledges receipt of a plot (only meaningful in reliable mode)
+    Ack { id: String },
+}
+
+/// Control frames sent by the server alongside plots.
+///
+/// Plot frames are bare `PlotMessage`s; control frames are told apart by `kind`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ServerFrame {
+    /// The server is about to close this connection; reconnect to resume
+    Reconnect { reason: String },
+    /// These plots passed their `expires_at` and were removed from history
+    Expired { ids: Vec<String> },
+    /// A plot was pinned or unpinned
+    Pinned { id: String, pinned: bool },
+    /// Every plot was removed from history
+    Cleared,
+    /// A plot's annotations were replaced with these
+    Annotated {
+        id: String,
+        annotations: HashMap<String, String>,
+    },
+    /// This plot couldn't be encoded and was skipped for this client
+    PlotFailed { id: String, error: String },
+    /// The session's title and description changed; also sent on connect
+    /// when any are set
+    SessionInfo(SessionInfo),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp_ns_distinguishes_back_to_back_plots() {
+        let first = PlotMessage::new(PlotContent::Svg("<svg/>".into()));
+        let second = PlotMessage::new(PlotContent::Svg("<svg/>".into()));
+        assert!(second.timestamp_ns > first.timestamp_ns);
+        assert_eq!(first.timestamp, first.timestamp_ns / 1_000_000);
+
+        let old: PlotMessage = serde_json::from_str(
+            r#"{"id":"a","timestamp":5,"content":{"type":"Svg","data":"<svg/>"}}"#,
+        )
+        .unwrap();
+        assert_eq!((old.timestamp, old.timestamp_ns), (5, 0));
+    }
 }
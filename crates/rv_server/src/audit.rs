@@ -0,0 +1,299 @@
+//! Audit trail of published plots: one JSON line per push with the server's
+//! time, the plot's id, content type, size, and how the publisher
+//! authenticated. The plot's own timestamp, which publishers may set, is
+//! recorded beside it but never decides where a line goes.
+//!
+//! Lines go to `<path>.YYYY-MM-DD` (UTC), starting a new file each day and
+//! whenever the current one reaches [`MAX_FILE_BYTES`] (`<path>.YYYY-MM-DD.1`,
+//! `.2`, ...). Writes happen on a background thread behind an unbounded
+//! queue, so auditing never waits on the disk in the publish path and no
+//! line is dropped when it falls behind; [`AuditLog::close`] writes out the
+//! rest on shutdown.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex},
+    thread::JoinHandle,
+};
+
+use anyhow::Context;
+use rv_core::PlotMessage;
+use serde::Serialize;
+use tracing::warn;
+
+/// Size at which the day's file is continued in a new one.
+const MAX_FILE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// How a publish was let in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Auth {
+    /// The server token, in the request
+    Token,
+    /// A browser session cookie from `/api/session`
+    Session,
+    /// The server has no token, so anyone may publish
+    Open,
+    /// [`ServerHandle`](crate::ServerHandle) in the same process
+    InProcess,
+}
+
+impl Auth {
+    /// Which credential let an `authorized` request in.
+    pub(crate) fn of(expected: &Option<String>, provided: Option<&str>) -> Self {
+        match expected.as_deref() {
+            None | Some("") => Auth::Open,
+            Some(_) if crate::token_valid(expected, provided) => Auth::Token,
+            Some(_) => Auth::Session,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Entry {
+    ts: String,
+    /// The plot's `timestamp`, as published
+    plot_ts: String,
+    id: String,
+    #[serde(rename = "type")]
+    content_type: &'static str,
+    bytes: usize,
+    auth: Auth,
+}
+
+enum Op {
+    /// An entry recorded at this Unix time in milliseconds
+    Record(u64, Entry),
+    Close,
+}
+
+#[derive(Clone)]
+pub(crate) struct AuditLog {
+    tx: mpsc::Sender<Op>,
+    /// Taken and joined by [`close`](Self::close)
+    thread: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+impl AuditLog {
+    /// Start the writer thread for files named after `path`.
+    pub(crate) fn spawn(path: PathBuf) -> anyhow::Result<Self> {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).with_context(|| format!("failed to create {dir:?}"))?;
+        }
+        let (tx, rx) = mpsc::channel::<Op>();
+        let thread = std::thread::Builder::new()
+            .name("rv-audit".into())
+            .spawn(move || {
+                let mut writer = Writer::new(path, MAX_FILE_BYTES);
+                let mut open = true;
+                while open {
+                    let Ok(first) = rx.recv() else {
+                        break;
+                    };
+                    // Drain whatever queued up meanwhile, then flush once
+                    for op in std::iter::once(first).chain(rx.try_iter()) {
+                        let Op::Record(timestamp, entry) = op else {
+                            open = false;
+                            break;
+                        };
+                        if let Err(e) = writer.write(timestamp, &entry) {
+                            warn!("Failed to write audit log entry for {}: {:#}", entry.id, e);
+                        }
+                    }
+                    if let Err(e) = writer.flush() {
+                        warn!("Failed to flush audit log: {:#}", e);
+                    }
+                }
+            })
+            .context("failed to spawn audit log thread")?;
+        Ok(Self {
+            tx,
+            thread: Arc::new(Mutex::new(Some(thread))),
+        })
+    }
+
+    pub(crate) fn record(&self, msg: &PlotMessage, auth: Auth) {
+        let now = rv_core::now_millis();
+        let entry = Entry {
+            ts: crate::rfc3339(now),
+            plot_ts: crate::rfc3339(msg.timestamp),
+            id: msg.id.clone(),
+            content_type: msg.content.type_name(),
+            bytes: msg.content.byte_len(),
+            auth,
+        };
+        // Send only fails once the writer thread is gone
+        let _ = self.tx.send(Op::Record(now, entry));
+    }
+
+    /// Write out every entry recorded so far and stop the writer thread.
+    pub(crate) async fn close(&self) {
+        let _ = self.tx.send(Op::Close);
+        let thread = self.thread.lock().unwrap().take();
+        if let Some(thread) = thread {
+            let _ = tokio::task::spawn_blocking(move || thread.join()).await;
+        }
+    }
+}
+
+struct Writer {
+    base: PathBuf,
+    max_bytes: u64,
+    /// `YYYY-MM-DD` and part number of the open file
+    day: String,
+    part: u32,
+    file: Option<BufWriter<File>>,
+    written: u64,
+}
+
+impl Writer {
+    fn new(base: PathBuf, max_bytes: u64) -> Self {
+        Self {
+            base,
+            max_bytes,
+            day: String::new(),
+            part: 0,
+            file: None,
+            written: 0,
+        }
+    }
+
+    fn write(&mut self, timestamp_ms: u64, entry: &Entry) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        let day = day_of(timestamp_ms);
+        if self.file.is_none() || day != self.day {
+            self.open(day, 0)?;
+        } else if self.written + line.len() as u64 > self.max_bytes && self.written > 0 {
+            self.open(self.day.clone(), self.part + 1)?;
+        }
+        let file = self.file.as_mut().expect("opened above");
+        file.write_all(&line)?;
+        self.written += line.len() as u64;
+        Ok(())
+    }
+
+    /// Open the first part of `day` from `part` on that still has room,
+    /// appending to it if a previous run left it behind.
+    fn open(&mut self, day: String, mut part: u32) -> anyhow::Result<()> {
+        self.flush()?;
+        let path = loop {
+            let path = file_name(&self.base, &day, part);
+            match fs::metadata(&path) {
+                Ok(meta) if meta.len() >= self.max_bytes => part += 1,
+                _ => break path,
+            }
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("failed to open {path:?}"))?;
+        self.written = file.metadata()?.len();
+        self.file = Some(BufWriter::new(file));
+        self.day = day;
+        self.part = part;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        if let Some(file) = &mut self.file {
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+fn day_of(timestamp_ms: u64) -> String {
+    time::OffsetDateTime::from_unix_timestamp_nanos(timestamp_ms as i128 * 1_000_000)
+        .map(|t| t.date().to_string())
+        .unwrap_or_default()
+}
+
+fn file_name(base: &Path, day: &str, part: u32) -> PathBuf {
+    let mut name = base.as_os_str().to_owned();
+    name.push(format!(".{day}"));
+    if part > 0 {
+        name.push(format!(".{part}"));
+    }
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotates_by_day_and_size() {
+        let dir = std::env::temp_dir().join(format!("rv_audit_{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let base = dir.join("audit.log");
+        let entry = |id: &str| Entry {
+            ts: String::new(),
+            plot_ts: String::new(),
+            id: id.to_string(),
+            content_type: "Svg",
+            bytes: 6,
+            auth: Auth::Token,
+        };
+        // 2026-10-14 and 2026-10-15, UTC
+        let (day1, day2) = (1_791_936_000_000, 1_792_022_400_000);
+        let line_len = serde_json::to_vec(&entry("aa")).unwrap().len() as u64 + 1;
+        let mut writer = Writer::new(base.clone(), 2 * line_len);
+        for (timestamp, id) in [(day1, "aa"), (day1, "bb"), (day1, "cc"), (day2, "dd")] {
+            writer.write(timestamp, &entry(id)).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let ids = |name: &str| -> Vec<String> {
+            fs::read_to_string(dir.join(name))
+                .unwrap()
+                .lines()
+                .map(|line| {
+                    let entry: serde_json::Value = serde_json::from_str(line).unwrap();
+                    entry["id"].as_str().unwrap().to_string()
+                })
+                .collect()
+        };
+        assert_eq!(ids("audit.log.2026-10-14"), ["aa", "bb"]);
+        assert_eq!(ids("audit.log.2026-10-14.1"), ["cc"]);
+        assert_eq!(ids("audit.log.2026-10-15"), ["dd"]);
+
+        // A restart on the same day skips the full part and appends to the next
+        let mut writer = Writer::new(base, 2 * line_len);
+        writer.write(day1, &entry("ee")).unwrap();
+        writer.flush().unwrap();
+        assert_eq!(ids("audit.log.2026-10-14.1"), ["cc", "ee"]);
+        assert_eq!(
+            fs::read_to_string(dir.join("audit.log.2026-10-15")).unwrap(),
+            concat!(
+                r#"{"ts":"","plot_ts":"","id":"dd","type":"Svg","bytes":6,"auth":"token"}"#,
+                "\n"
+            )
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_backdated_plot_is_logged_under_server_time() {
+        let dir = std::env::temp_dir().join(format!("rv_audit_{}", uuid::Uuid::new_v4()));
+        let base = dir.join("audit.log");
+        let log = AuditLog::spawn(base.clone()).unwrap();
+        let mut msg = PlotMessage::new(rv_core::PlotContent::Svg("<svg/>".into()));
+        msg.timestamp = 0;
+        log.record(&msg, Auth::Open);
+        log.close().await;
+
+        let today = file_name(&base, &day_of(rv_core::now_millis()), 0);
+        let line = fs::read_to_string(today).unwrap();
+        let entry: serde_json::Value = serde_json::from_str(line.trim()).unwrap();
+        assert_eq!(entry["id"], msg.id);
+        assert_eq!(entry["plot_ts"], crate::rfc3339(0));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
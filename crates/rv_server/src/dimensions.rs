@@ -0,0 +1,123 @@
+//! Intrinsic pixel size of image plots, worked out cheaply at publish time.
+//!
+//! Raster formats are read from a decoded prefix of the payload, which holds
+//! the header for any image that isn't padded with huge metadata blocks; SVGs
+//! from the root element's `width`/`height`, falling back to its `viewBox`.
+//! Anything unclear yields `None` rather than a guess.
+
+use std::io::Cursor;
+
+use base64::Engine;
+use image::{ImageFormat, ImageReader};
+use rv_core::PlotContent;
+
+/// Base64 characters decoded when sniffing a raster header (48 KiB of image).
+const HEADER_PREFIX: usize = 64 * 1024;
+
+pub(crate) fn of(content: &PlotContent) -> Option<(u32, u32)> {
+    match content {
+        PlotContent::Png(data) => raster(data, ImageFormat::Png),
+        PlotContent::Jpeg(data) => raster(data, ImageFormat::Jpeg),
+        PlotContent::Webp(data) => raster(data, ImageFormat::WebP),
+        PlotContent::Gif(data) => raster(data, ImageFormat::Gif),
+        PlotContent::Svg(data) => svg(data),
+        _ => None,
+    }
+}
+
+fn raster(data: &str, format: ImageFormat) -> Option<(u32, u32)> {
+    // Whole base64 quads only, so the prefix decodes on its own
+    let end = data.len().min(HEADER_PREFIX) / 4 * 4;
+    let prefix = data.get(..end)?.trim_end_matches('=');
+    let bytes = base64::engine::general_purpose::STANDARD_NO_PAD
+        .decode(prefix)
+        .ok()?;
+    let reader = ImageReader::with_format(Cursor::new(bytes), format);
+    reader.into_dimensions().ok()
+}
+
+fn svg(data: &str) -> Option<(u32, u32)> {
+    let start = data.find("<svg")?;
+    let tag = &data[start..];
+    let tag = &tag[..tag.find('>')?];
+    let width = attr(tag, "width").and_then(length);
+    let height = attr(tag, "height").and_then(length);
+    if let (Some(width), Some(height)) = (width, height) {
+        return Some((width, height));
+    }
+    let view_box: Vec<f64> = attr(tag, "viewBox")?
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|part| !part.is_empty())
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .ok()?;
+    match view_box[..] {
+        [_, _, width, height] => Some((pixels(width)?, pixels(height)?)),
+        _ => None,
+    }
+}
+
+/// The value of attribute `name` in the opening tag `tag`.
+fn attr<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = tag;
+    while let Some(i) = rest.find(name) {
+        let before = rest[..i].chars().next_back();
+        let after = rest[i + name.len()..].trim_start();
+        rest = &rest[i + name.len()..];
+        // Skip matches inside longer names, like `stroke-width`
+        if !before.is_some_and(char::is_whitespace) {
+            continue;
+        }
+        let Some(value) = after.strip_prefix('=') else {
+            continue;
+        };
+        let value = value.trim_start();
+        let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let value = &value[1..];
+        return value.find(quote).map(|end| &value[..end]);
+    }
+    None
+}
+
+/// A width or height in user units or `px`; relative units can't be resolved here.
+fn length(value: &str) -> Option<u32> {
+    let value = value.trim();
+    let value = value.strip_suffix("px").unwrap_or(value);
+    pixels(value.parse().ok()?)
+}
+
+fn pixels(value: f64) -> Option<u32> {
+    (value.is_finite() && value >= 1.0 && value <= u32::MAX as f64).then(|| value.round() as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dimensions_of_images() {
+        let mut png = Vec::new();
+        image::RgbImage::new(3, 2)
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        let png = base64::engine::general_purpose::STANDARD.encode(png);
+        assert_eq!(of(&PlotContent::Png(png)), Some((3, 2)));
+        assert_eq!(of(&PlotContent::Png("not an image".into())), None);
+
+        let svg = |s: &str| of(&PlotContent::Svg(s.into()));
+        assert_eq!(
+            svg(r#"<svg stroke-width="2" width="640px" height='480'>"#),
+            Some((640, 480))
+        );
+        assert_eq!(
+            svg(r#"<?xml?><svg viewBox="0 0 300.4 150">"#),
+            Some((300, 150))
+        );
+        assert_eq!(
+            svg(r#"<svg width="100%" height="100%" viewBox="0,0,40,30"/>"#),
+            Some((40, 30))
+        );
+        assert_eq!(svg(r#"<svg width="10em" height="5em">"#), None);
+        assert_eq!(svg("<svg>"), None);
+    }
+}
@@ -0,0 +1,142 @@
+//! Recently seen `Idempotency-Key`s, so a publisher that retries a POST whose
+//! response was lost gets the original answer back instead of a duplicate plot.
+//!
+//! A key is reserved before its publish runs and filled in with the response
+//! once it succeeds. A repeat while the first is still running is told so
+//! rather than waiting; a publish that fails or is abandoned releases its key
+//! so the retry can go through. Each key also remembers a fingerprint of its
+//! request, so reusing a key for a different request is caught rather than
+//! answered with the first one's response.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Keys remembered at once; past this the least recently used is forgotten.
+const MAX_KEYS: usize = 1024;
+/// How long a key is remembered after it was last used.
+const KEY_TTL: Duration = Duration::from_secs(60 * 60);
+
+enum Slot<T> {
+    Pending,
+    Done(T),
+}
+
+struct Entry<T> {
+    slot: Slot<T>,
+    fingerprint: u64,
+    used: Instant,
+}
+
+pub(crate) enum Seen<T> {
+    /// First use: run the publish and [`Reservation::complete`] it
+    New(Reservation<T>),
+    /// The first request with this key hasn't finished yet
+    InProgress,
+    /// The response the first request got
+    Done(T),
+    /// The key was first used for a request with a different fingerprint
+    Mismatch,
+}
+
+pub(crate) struct IdempotencyKeys<T> {
+    keys: Arc<Mutex<HashMap<String, Entry<T>>>>,
+}
+
+impl<T> Clone for IdempotencyKeys<T> {
+    fn clone(&self) -> Self {
+        Self {
+            keys: self.keys.clone(),
+        }
+    }
+}
+
+impl<T> Default for IdempotencyKeys<T> {
+    fn default() -> Self {
+        Self {
+            keys: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl<T: Clone> IdempotencyKeys<T> {
+    /// Look up `key` for a request hashing to `fingerprint`.
+    pub(crate) fn check(&self, key: &str, fingerprint: u64) -> Seen<T> {
+        let now = Instant::now();
+        let mut keys = self.keys.lock().unwrap();
+        match keys.get_mut(key) {
+            Some(entry) if now.duration_since(entry.used) < KEY_TTL => {
+                if entry.fingerprint != fingerprint {
+                    return Seen::Mismatch;
+                }
+                entry.used = now;
+                return match &entry.slot {
+                    Slot::Pending => Seen::InProgress,
+                    Slot::Done(value) => Seen::Done(value.clone()),
+                };
+            }
+            _ => {}
+        }
+        if keys.len() >= MAX_KEYS {
+            keys.retain(|_, entry| now.duration_since(entry.used) < KEY_TTL);
+        }
+        if keys.len() >= MAX_KEYS {
+            let oldest = keys
+                .iter()
+                .min_by_key(|(_, entry)| entry.used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                keys.remove(&oldest);
+            }
+        }
+        let entry = Entry {
+            slot: Slot::Pending,
+            fingerprint,
+            used: now,
+        };
+        keys.insert(key.to_string(), entry);
+        Seen::New(Reservation {
+            keys: self.keys.clone(),
+            key: key.to_string(),
+            fingerprint,
+            done: false,
+        })
+    }
+}
+
+/// A key held for a publish in progress; released on drop unless completed.
+pub(crate) struct Reservation<T> {
+    keys: Arc<Mutex<HashMap<String, Entry<T>>>>,
+    key: String,
+    fingerprint: u64,
+    done: bool,
+}
+
+impl<T> Reservation<T> {
+    /// Remember `value` as the answer to every repeat of this key.
+    pub(crate) fn complete(mut self, value: T) {
+        let entry = Entry {
+            slot: Slot::Done(value),
+            fingerprint: self.fingerprint,
+            used: Instant::now(),
+        };
+        self.keys.lock().unwrap().insert(self.key.clone(), entry);
+        self.done = true;
+    }
+}
+
+impl<T> Drop for Reservation<T> {
+    fn drop(&mut self) {
+        if !self.done {
+            let mut keys = self.keys.lock().unwrap();
+            if keys
+                .get(&self.key)
+                .is_some_and(|entry| matches!(entry.slot, Slot::Pending))
+            {
+                keys.remove(&self.key);
+            }
+        }
+    }
+}
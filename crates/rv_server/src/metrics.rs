@@ -0,0 +1,188 @@
+//! Prometheus text exposition for `/metrics`.
+//!
+//! Publish latency and size are kept in fixed-bucket histograms of atomic
+//! counters, so recording is a few relaxed increments with no lock. Like any
+//! Prometheus histogram they are cumulative since startup and never reset by
+//! a scrape; the quantile gauges are estimated from the same buckets for
+//! quick reading without a Prometheus server.
+
+use std::{
+    fmt::Write,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Upper bucket bounds in microseconds, 50µs to 10s.
+const BOUNDS_MICROS: [u64; 17] = [
+    50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000, 250_000, 500_000,
+    1_000_000, 2_500_000, 5_000_000, 10_000_000,
+];
+
+/// Upper bucket bounds in bytes, 256B (a tiny SVG) to 256MiB (a long
+/// animation), each 4x the last.
+const BOUNDS_BYTES: [u64; 11] = [
+    1 << 8,
+    1 << 10,
+    1 << 12,
+    1 << 14,
+    1 << 16,
+    1 << 18,
+    1 << 20,
+    1 << 22,
+    1 << 24,
+    1 << 26,
+    1 << 28,
+];
+
+const QUANTILES: [f64; 3] = [0.5, 0.95, 0.99];
+
+pub(crate) struct Histogram {
+    /// Upper bucket bounds in recorded units
+    bounds: &'static [u64],
+    /// Reported value of one recorded unit, e.g. 1e-6 for microseconds as seconds
+    scale: f64,
+    /// Per-bucket (not cumulative) counts; the last slot is `+Inf`
+    buckets: Vec<AtomicU64>,
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [u64], scale: f64) -> Self {
+        Self {
+            bounds,
+            scale,
+            buckets: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            sum: AtomicU64::new(0),
+        }
+    }
+
+    /// Durations, reported in seconds.
+    pub(crate) fn latency() -> Self {
+        Self::new(&BOUNDS_MICROS, 1e-6)
+    }
+
+    /// Payload sizes, reported in bytes.
+    pub(crate) fn bytes() -> Self {
+        Self::new(&BOUNDS_BYTES, 1.0)
+    }
+
+    pub(crate) fn record_duration(&self, elapsed: Duration) {
+        self.record(u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX));
+    }
+
+    pub(crate) fn record(&self, value: u64) {
+        let bucket = self.bounds.partition_point(|&bound| bound < value);
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value, Ordering::Relaxed);
+    }
+
+    fn counts(&self) -> Vec<u64> {
+        self.buckets
+            .iter()
+            .map(|b| b.load(Ordering::Relaxed))
+            .collect()
+    }
+
+    /// Estimate the `q` quantile in reported units by interpolating within
+    /// its bucket, as PromQL's `histogram_quantile` does. `None` before any samples.
+    fn quantile(&self, counts: &[u64], q: f64) -> Option<f64> {
+        let bounds = self.bounds;
+        let scaled = |value: u64| value as f64 * self.scale;
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let rank = q * total as f64;
+        let mut seen = 0;
+        for (i, &count) in counts.iter().enumerate() {
+            if count > 0 && (seen + count) as f64 >= rank {
+                // Samples past the last bound are reported at that bound
+                let Some(&upper) = bounds.get(i) else {
+                    return Some(scaled(bounds[bounds.len() - 1]));
+                };
+                let lower = if i == 0 { 0 } else { bounds[i - 1] };
+                let fraction = (rank - seen as f64) / count as f64;
+                return Some(scaled(lower) + (scaled(upper) - scaled(lower)) * fraction);
+            }
+            seen += count;
+        }
+        None
+    }
+
+    /// Append this histogram as `name`, plus a `<name>_quantile` gauge.
+    pub(crate) fn write_prometheus(&self, out: &mut String, name: &str, help: &str) {
+        let counts = self.counts();
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} histogram");
+        let mut cumulative = 0;
+        for (bound, count) in self.bounds.iter().zip(&counts) {
+            cumulative += count;
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{le=\"{}\"}} {cumulative}",
+                *bound as f64 * self.scale
+            );
+        }
+        cumulative += counts[self.bounds.len()];
+        let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {cumulative}");
+        let sum = self.sum.load(Ordering::Relaxed) as f64 * self.scale;
+        let _ = writeln!(out, "{name}_sum {sum}");
+        let _ = writeln!(out, "{name}_count {cumulative}");
+
+        let _ = writeln!(
+            out,
+            "# HELP {name}_quantile {help}, estimated from the histogram buckets"
+        );
+        let _ = writeln!(out, "# TYPE {name}_quantile gauge");
+        for q in QUANTILES {
+            let value = self.quantile(&counts, q).unwrap_or(f64::NAN);
+            let _ = writeln!(out, "{name}_quantile{{quantile=\"{q}\"}} {value}");
+        }
+    }
+}
+
+/// Append a single-sample gauge.
+pub(crate) fn write_gauge(out: &mut String, name: &str, help: &str, value: u64) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} gauge");
+    let _ = writeln!(out, "{name} {value}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantiles_interpolate_within_buckets() {
+        let histogram = Histogram::latency();
+        assert_eq!(histogram.quantile(&histogram.counts(), 0.5), None);
+        // 90 fast publishes in (500µs, 1ms], 10 slow ones in (50ms, 100ms]
+        for _ in 0..90 {
+            histogram.record_duration(Duration::from_micros(800));
+        }
+        for _ in 0..10 {
+            histogram.record_duration(Duration::from_millis(80));
+        }
+        let counts = histogram.counts();
+        let p50 = histogram.quantile(&counts, 0.5).unwrap();
+        assert!(p50 > 0.0005 && p50 <= 0.001, "p50 {p50}");
+        let p99 = histogram.quantile(&counts, 0.99).unwrap();
+        assert!(p99 > 0.05 && p99 <= 0.1, "p99 {p99}");
+
+        let mut out = String::new();
+        histogram.write_prometheus(&mut out, "t", "test");
+        assert!(out.contains("t_bucket{le=\"0.001\"} 90\n"));
+        assert!(out.contains("t_bucket{le=\"+Inf\"} 100\n"));
+        assert!(out.contains("t_count 100\n"));
+
+        let sizes = Histogram::bytes();
+        sizes.record(300);
+        sizes.record(300 << 20);
+        let mut out = String::new();
+        sizes.write_prometheus(&mut out, "b", "test");
+        assert!(out.contains("b_bucket{le=\"256\"} 0\n"));
+        assert!(out.contains("b_bucket{le=\"1024\"} 1\n"));
+        assert!(out.contains("b_bucket{le=\"268435456\"} 1\n"));
+        assert!(out.contains("b_bucket{le=\"+Inf\"} 2\n"));
+    }
+}
@@ -0,0 +1,530 @@
+//! On-disk history: one JSON `PlotMessage` per line, compressed if the file
+//! name ends in `.gz` or `.zst` (see [`rv_core::compress`]).
+//!
+//! New plots are appended, and so are annotation changes, as a line naming
+//! the plot they relabel. Once evictions and patches leave the file holding
+//! well over the history limit, it is compacted by writing the live history to a temp file
+//! and renaming it over the original, so a crash mid-rewrite leaves either the
+//! old file or the new one, never a truncated mix.
+//!
+//! The session's [`SessionInfo`] lives beside the history, in
+//! `<history file>.session.json`, and is replaced the same way.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+};
+
+use anyhow::Context;
+use rv_core::{PlotMessage, SessionInfo};
+use serde::{Deserialize, Serialize};
+
+use rv_core::compress::Codec;
+use tokio::sync::{mpsc, oneshot};
+use tracing::warn;
+
+enum Op {
+    Append(Arc<PlotMessage>),
+    /// Append an [`AnnotationPatch`] with this plot's annotations
+    Annotate(Arc<PlotMessage>),
+    Rewrite(Vec<Arc<PlotMessage>>),
+    SessionInfo(SessionInfo),
+    /// Answered once every op before it is done: `false` if any since the
+    /// previous `Flush` failed
+    Flush(oneshot::Sender<bool>),
+    /// Stop once every op before it is done
+    Close,
+}
+
+/// Queues writes to a background thread so disk I/O (and fsync) never blocks
+/// the async workers. Ops are sent under the history lock, so they land in
+/// the same order the history changed; [`flush`](Self::flush) waits for them.
+#[derive(Clone)]
+pub(crate) struct Persister {
+    tx: mpsc::UnboundedSender<Op>,
+    /// Lines currently in the file, evicted plots included
+    lines: Arc<AtomicUsize>,
+    /// Every write is fsynced, so a flush means it's on disk
+    durable: bool,
+    /// Taken and joined by [`close`](Self::close)
+    thread: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+impl Persister {
+    /// Open (or create) the history file, returning the plots it holds.
+    pub(crate) fn open(
+        path: &Path,
+        durable: bool,
+    ) -> anyhow::Result<(Self, Vec<Arc<PlotMessage>>)> {
+        let mut store = Store::open(path, durable)?;
+        let history = store.load_repairing()?;
+        let lines = Arc::new(AtomicUsize::new(history.len()));
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let thread = std::thread::Builder::new()
+            .name("rv-persist".into())
+            .spawn(move || {
+                let mut failed = false;
+                while let Some(op) = rx.blocking_recv() {
+                    let result = match op {
+                        Op::Append(msg) => store.append(&msg),
+                        Op::Annotate(msg) => store.append(&AnnotationPatch::of(&msg)),
+                        Op::Rewrite(history) => store.rewrite(&history),
+                        Op::SessionInfo(info) => store.save_session_info(&info),
+                        Op::Flush(done) => {
+                            let _ = done.send(!std::mem::take(&mut failed));
+                            continue;
+                        }
+                        Op::Close => break,
+                    };
+                    if let Err(e) = result {
+                        warn!("Failed to persist history to {:?}: {:#}", store.path, e);
+                        failed = true;
+                    }
+                }
+            })
+            .context("failed to spawn persistence thread")?;
+        let persister = Self {
+            tx,
+            lines,
+            durable,
+            thread: Arc::new(Mutex::new(Some(thread))),
+        };
+        Ok((persister, history))
+    }
+
+    pub(crate) fn durable(&self) -> bool {
+        self.durable
+    }
+
+    /// Wait until everything recorded so far is written (and fsynced, if
+    /// durable); `false` if a write failed or the writer has stopped.
+    pub(crate) async fn flush(&self) -> bool {
+        let (done, written) = oneshot::channel();
+        if self.tx.send(Op::Flush(done)).is_err() {
+            return false;
+        }
+        written.await.unwrap_or(false)
+    }
+
+    /// Write out everything queued and stop the writer thread. Later
+    /// changes are no longer persisted.
+    pub(crate) async fn close(&self) {
+        let _ = self.tx.send(Op::Close);
+        let thread = self.thread.lock().unwrap().take();
+        if let Some(thread) = thread {
+            let _ = tokio::task::spawn_blocking(move || thread.join()).await;
+        }
+    }
+
+    /// Record a newly pushed plot, compacting instead once the file holds more
+    /// than twice the live history.
+    pub(crate) fn record(&self, msg: &Arc<PlotMessage>, history: &VecDeque<Arc<PlotMessage>>) {
+        self.append_or_compact(Op::Append(msg.clone()), history);
+    }
+
+    /// Record `msg`'s replaced annotations, compacting like [`record`](Self::record).
+    pub(crate) fn annotate(&self, msg: &Arc<PlotMessage>, history: &VecDeque<Arc<PlotMessage>>) {
+        self.append_or_compact(Op::Annotate(msg.clone()), history);
+    }
+
+    fn append_or_compact(&self, append: Op, history: &VecDeque<Arc<PlotMessage>>) {
+        let lines = self.lines.fetch_add(1, Ordering::Relaxed) + 1;
+        let op = if lines > 2 * history.len().max(1) {
+            self.lines.store(history.len(), Ordering::Relaxed);
+            Op::Rewrite(history.iter().cloned().collect())
+        } else {
+            append
+        };
+        // Send only fails once the writer thread is gone, and it already logged why
+        let _ = self.tx.send(op);
+    }
+
+    /// Replace the file contents with exactly `history`.
+    pub(crate) fn rewrite(&self, history: &VecDeque<Arc<PlotMessage>>) {
+        self.lines.store(history.len(), Ordering::Relaxed);
+        let _ = self.tx.send(Op::Rewrite(history.iter().cloned().collect()));
+    }
+
+    /// Replace the saved session info, removing the file once it's empty.
+    pub(crate) fn session_info(&self, info: &SessionInfo) {
+        let _ = self.tx.send(Op::SessionInfo(info.clone()));
+    }
+}
+
+struct Store {
+    path: PathBuf,
+    /// fsync every append and rewrite before reporting success
+    durable: bool,
+    codec: Codec,
+    file: File,
+}
+
+impl Store {
+    fn open(path: &Path, durable: bool) -> anyhow::Result<Self> {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).with_context(|| format!("failed to create {dir:?}"))?;
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            durable,
+            codec: Codec::for_path(path),
+            file: open_append(path)?,
+        })
+    }
+
+    /// Read every intact line, rewriting the file without any corrupt ones
+    /// so later appends start on a clean line.
+    fn load_repairing(&mut self) -> anyhow::Result<Vec<Arc<PlotMessage>>> {
+        let (history, corrupt) = read_lines(&self.path)?;
+        if corrupt {
+            self.rewrite(&history)?;
+        }
+        Ok(history)
+    }
+
+    fn append(&mut self, line: &impl Serialize) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(line)?;
+        line.push(b'\n');
+        self.file.write_all(&self.codec.encode(line)?)?;
+        if self.durable {
+            self.file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    fn rewrite(&mut self, history: &[Arc<PlotMessage>]) -> anyhow::Result<()> {
+        let tmp = self.path.with_extension("tmp");
+        write_snapshot(&tmp, history, self.codec, self.durable)?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("failed to replace {:?}", self.path))?;
+        if self.durable {
+            sync_parent_dir(&self.path)?;
+        }
+        // The old handle points at the replaced inode
+        self.file = open_append(&self.path)?;
+        Ok(())
+    }
+
+    fn save_session_info(&self, info: &SessionInfo) -> anyhow::Result<()> {
+        let path = session_info_path(&self.path);
+        if info.is_empty() {
+            return match fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    Err(e).with_context(|| format!("failed to remove {path:?}"))
+                }
+                _ => Ok(()),
+            };
+        }
+        let tmp = path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        serde_json::to_writer(&mut file, info)?;
+        if self.durable {
+            file.sync_all()?;
+        }
+        fs::rename(&tmp, &path).with_context(|| format!("failed to replace {path:?}"))?;
+        if self.durable {
+            sync_parent_dir(&path)?;
+        }
+        Ok(())
+    }
+}
+
+/// A line replacing the annotations of the newest plot before it with id
+/// `annotate`, so relabeling a plot doesn't rewrite the whole file.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AnnotationPatch {
+    annotate: String,
+    annotations: HashMap<String, String>,
+}
+
+impl AnnotationPatch {
+    fn of(msg: &PlotMessage) -> Self {
+        Self {
+            annotate: msg.id.clone(),
+            annotations: msg.annotations.clone(),
+        }
+    }
+
+    fn apply(self, history: &mut [Arc<PlotMessage>]) {
+        if let Some(msg) = history.iter_mut().rev().find(|msg| msg.id == self.annotate) {
+            Arc::make_mut(msg).annotations = self.annotations;
+        }
+    }
+}
+
+/// Read a history file without modifying it, e.g. to serve it as an archive.
+pub(crate) fn load(path: &Path) -> anyhow::Result<Vec<Arc<PlotMessage>>> {
+    Ok(read_lines(path)?.0)
+}
+
+/// Read the session info saved beside the history file at `path`, if any.
+pub(crate) fn load_session_info(path: &Path) -> anyhow::Result<SessionInfo> {
+    let path = session_info_path(path);
+    match fs::read(&path) {
+        Ok(bytes) => {
+            serde_json::from_slice(&bytes).with_context(|| format!("failed to parse {path:?}"))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(SessionInfo::default()),
+        Err(e) => Err(e).with_context(|| format!("failed to read {path:?}")),
+    }
+}
+
+fn session_info_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".session.json");
+    PathBuf::from(name)
+}
+
+/// Parse every intact line, and report whether any were skipped. A torn
+/// final line (crash during append) or other unparseable line is logged
+/// rather than failing startup.
+fn read_lines(path: &Path) -> anyhow::Result<(Vec<Arc<PlotMessage>>, bool)> {
+    let file = File::open(path).with_context(|| format!("failed to open {path:?}"))?;
+    let codec = Codec::for_path(path);
+    let mut history = Vec::new();
+    let mut corrupt = false;
+    let reader = codec
+        .reader(file)
+        .with_context(|| format!("failed to read {path:?}"))?;
+    for (n, line) in reader.lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            // A torn gzip member or zstd frame only ever ends the file
+            Err(e) if codec != Codec::Plain => {
+                warn!("Skipping unreadable end of {:?}: {}", path, e);
+                corrupt = true;
+                break;
+            }
+            Err(e) => return Err(e).with_context(|| format!("failed to read {path:?}")),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(msg) => history.push(Arc::new(msg)),
+            Err(e) => match serde_json::from_str::<AnnotationPatch>(&line) {
+                Ok(patch) => patch.apply(&mut history),
+                Err(_) => {
+                    warn!("Skipping corrupt line {} in {:?}: {}", n + 1, path, e);
+                    corrupt = true;
+                }
+            },
+        }
+    }
+    Ok((history, corrupt))
+}
+
+fn open_append(path: &Path) -> anyhow::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open {path:?}"))
+}
+
+fn write_snapshot(
+    path: &Path,
+    history: &[Arc<PlotMessage>],
+    codec: Codec,
+    durable: bool,
+) -> anyhow::Result<()> {
+    let mut out = codec.writer(File::create(path)?)?;
+    for msg in history {
+        serde_json::to_writer(&mut out, msg.as_ref())?;
+        out.write_all(b"\n")?;
+    }
+    let file = out.finish()?;
+    if durable {
+        file.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Make the rename itself durable; a no-op where directories can't be opened.
+fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        File::open(dir)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rv_core::PlotContent;
+
+    fn plots(n: usize) -> Vec<Arc<PlotMessage>> {
+        (0..n)
+            .map(|i| {
+                Arc::new(PlotMessage::new(PlotContent::Svg(format!(
+                    "<svg>{i}</svg>"
+                ))))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_interrupted_writes_keep_last_good_state() {
+        let dir = std::env::temp_dir().join(format!("rv_persist_{}", uuid::Uuid::new_v4()));
+        let path = dir.join("history.ndjson");
+        let good = plots(3);
+        {
+            let mut store = Store::open(&path, true).unwrap();
+            store.rewrite(&good).unwrap();
+        }
+
+        // Crash mid-rewrite: a half-written temp file that was never renamed
+        let newer = plots(5);
+        write_snapshot(&path.with_extension("tmp"), &newer, Codec::Plain, false).unwrap();
+        let tmp = path.with_extension("tmp");
+        let len = fs::metadata(&tmp).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&tmp)
+            .unwrap()
+            .set_len(len / 2)
+            .unwrap();
+
+        // Crash mid-append: a torn line at the end of the live file
+        let mut torn = serde_json::to_vec(newer[0].as_ref()).unwrap();
+        torn.truncate(torn.len() / 2);
+        open_append(&path).unwrap().write_all(&torn).unwrap();
+
+        let ids = |history: &[Arc<PlotMessage>]| -> Vec<String> {
+            history.iter().map(|msg| msg.id.clone()).collect()
+        };
+        let mut store = Store::open(&path, true).unwrap();
+        assert_eq!(ids(&store.load_repairing().unwrap()), ids(&good));
+
+        // The torn line was dropped, so appends after recovery stay readable
+        store.append(&newer[4]).unwrap();
+        let mut expected = ids(&good);
+        expected.push(newer[4].id.clone());
+        assert_eq!(ids(&store.load_repairing().unwrap()), expected);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_round_trip_each_codec() {
+        let dir = std::env::temp_dir().join(format!("rv_persist_{}", uuid::Uuid::new_v4()));
+        let ids = |history: &[Arc<PlotMessage>]| -> Vec<String> {
+            history.iter().map(|msg| msg.id.clone()).collect()
+        };
+        for (name, codec) in [
+            ("history.ndjson", Codec::Plain),
+            ("history.ndjson.gz", Codec::Gzip),
+            ("history.ndjson.zst", Codec::Zstd),
+        ] {
+            let path = dir.join(name);
+            assert_eq!(Codec::for_path(&path), codec);
+            let history = plots(4);
+            {
+                let mut store = Store::open(&path, false).unwrap();
+                store.rewrite(&history[..2]).unwrap();
+                store.append(&history[2]).unwrap();
+                store.append(&history[3]).unwrap();
+            }
+            assert_eq!(ids(&load(&path).unwrap()), ids(&history), "{name}");
+
+            // Crash mid-append: a torn final chunk loses only that plot
+            let extra = plots(1);
+            let chunk = codec
+                .encode(serde_json::to_vec(extra[0].as_ref()).unwrap())
+                .unwrap();
+            open_append(&path)
+                .unwrap()
+                .write_all(&chunk[..chunk.len() / 2])
+                .unwrap();
+            let mut store = Store::open(&path, false).unwrap();
+            assert_eq!(
+                ids(&store.load_repairing().unwrap()),
+                ids(&history),
+                "{name}"
+            );
+        }
+        let plain = fs::metadata(dir.join("history.ndjson")).unwrap().len();
+        for name in ["history.ndjson.gz", "history.ndjson.zst"] {
+            let compressed = fs::metadata(dir.join(name)).unwrap().len();
+            assert!(compressed < plain, "{name}: {compressed} >= {plain}");
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_close_writes_out_queued_plots() {
+        let dir = std::env::temp_dir().join(format!("rv_persist_{}", uuid::Uuid::new_v4()));
+        let path = dir.join("history.ndjson");
+        let (persister, saved) = Persister::open(&path, true).unwrap();
+        assert!(saved.is_empty());
+        let mut history = VecDeque::new();
+        for msg in plots(3) {
+            history.push_back(msg.clone());
+            persister.record(&msg, &history);
+        }
+        assert!(persister.flush().await);
+        persister.record(&history[0], &history);
+        persister.close().await;
+        assert_eq!(load(&path).unwrap().len(), 4);
+        // Nothing is left to answer a flush once closed
+        assert!(!persister.flush().await);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_annotation_patches_apply_on_load() {
+        let dir = std::env::temp_dir().join(format!("rv_persist_{}", uuid::Uuid::new_v4()));
+        let path = dir.join("history.ndjson");
+        let history = plots(2);
+        let mut store = Store::open(&path, false).unwrap();
+        store.rewrite(&history).unwrap();
+        let mut relabeled = PlotMessage::clone(&history[0]);
+        relabeled.annotations.insert("run".into(), "7".into());
+        store.append(&AnnotationPatch::of(&relabeled)).unwrap();
+
+        // One more line rather than a rewrite
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 3);
+        let loaded = store.load_repairing().unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].annotations, relabeled.annotations);
+        assert!(loaded[1].annotations.is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_session_info_saved_beside_history() {
+        let dir = std::env::temp_dir().join(format!("rv_persist_{}", uuid::Uuid::new_v4()));
+        let path = dir.join("history.ndjson");
+        let store = Store::open(&path, false).unwrap();
+        assert_eq!(load_session_info(&path).unwrap(), SessionInfo::default());
+
+        let info = SessionInfo {
+            title: Some("Nightly run".into()),
+            description: None,
+        };
+        store.save_session_info(&info).unwrap();
+        assert!(dir.join("history.ndjson.session.json").exists());
+        assert_eq!(load_session_info(&path).unwrap(), info);
+
+        // Clearing it removes the file rather than saving an empty one
+        store.save_session_info(&SessionInfo::default()).unwrap();
+        assert!(!dir.join("history.ndjson.session.json").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,82 @@
+//! Allowlist sanitizer for `Html` plots from publishers that aren't fully trusted.
+//!
+//! This is [`ammonia`]'s default policy with URLs limited to http(s) and
+//! mailto: the input is parsed as a browser would and re-serialized, keeping
+//! only allowed tags and attributes. `<script>` and `<style>` go along with
+//! their contents, as do event handlers and `style` attributes.
+
+use std::{collections::HashSet, sync::LazyLock};
+
+use rv_core::PlotContent;
+
+static CLEANER: LazyLock<ammonia::Builder<'static>> = LazyLock::new(|| {
+    let mut cleaner = ammonia::Builder::default();
+    cleaner.url_schemes(HashSet::from(["http", "https", "mailto"]));
+    cleaner
+});
+
+/// Sanitize `Html` content in place, `Compare` panes included.
+pub(crate) fn content(content: &mut PlotContent) {
+    match content {
+        PlotContent::Html(html) => *html = sanitize(html),
+        PlotContent::Compare { left, right, .. } => {
+            self::content(left);
+            self::content(right);
+        }
+        _ => {}
+    }
+}
+
+pub(crate) fn sanitize(html: &str) -> String {
+    CLEANER.clean(html).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_strips_active_content() {
+        let cases = [
+            (
+                r#"<p onclick="steal()">Hi<script>alert(1)</script> <b>there</b></p>"#,
+                "<p>Hi <b>there</b></p>",
+            ),
+            (
+                r#"<a href="javascript:alert(1)" title='t'>x</a><a href="/page">y</a>"#,
+                r#"<a title="t" rel="noopener noreferrer">x</a><a href="/page" rel="noopener noreferrer">y</a>"#,
+            ),
+            (
+                "<SCRIPT >x</script >1 < 2 &amp; <!-- c --><style>p{}</style>",
+                "1 &lt; 2 &amp; ",
+            ),
+            (
+                "<table><tr><td colspan=2>&</td></tr>",
+                r#"<table><tbody><tr><td colspan="2">&amp;</td></tr></tbody></table>"#,
+            ),
+            // Known bypasses of hand-rolled filters
+            (
+                r#"<img/src=x/onerror=alert(1)>"#,
+                r#"<img src="x/onerror=alert(1)">"#,
+            ),
+            ("<scr<script>ipt>alert(1)</script>", "ipt&gt;alert(1)"),
+            ("<script\t>alert(1)</script\t>ok", "ok"),
+            (r#"<a title="x>y<script>alert(1)</script>"#, ""),
+            (
+                r#"<a href="&#x6a;avascript:alert(1)">z</a>"#,
+                r#"<a rel="noopener noreferrer">z</a>"#,
+            ),
+            (
+                "<a href=java&#115;cript:x>z</a>",
+                r#"<a rel="noopener noreferrer">z</a>"#,
+            ),
+            (
+                "<a href=\" \x01javascript:x\">z</a>",
+                r#"<a rel="noopener noreferrer">z</a>"#,
+            ),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(sanitize(input), expected, "{input}");
+        }
+    }
+}
@@ -0,0 +1,260 @@
+//! Forwarding published plots to an upstream rileyviewer (hub and spoke).
+//!
+//! Unlike the webhook, delivery keeps retrying while the upstream is down:
+//! plots wait in a bounded buffer, oldest dropped first once it fills, and
+//! are replayed in order through the upstream's `/api/publish` with their
+//! original id and timestamp once it is reachable again. Later annotation
+//! changes follow the same queue as `PATCH /api/plots/:id`.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Condvar, Mutex},
+    time::Duration,
+};
+
+use anyhow::Context;
+use rv_config::UpstreamConfig;
+use rv_core::PlotMessage;
+use serde::Serialize;
+use tracing::{debug, info, warn};
+
+/// Plots held while the upstream is unreachable.
+const BUFFER_LIMIT: usize = 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Backoff after a failed delivery, doubling up to [`MAX_BACKOFF`].
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Clone)]
+pub(crate) struct Upstream {
+    queue: Arc<Queue>,
+}
+
+#[derive(Default)]
+struct Queue {
+    plots: Mutex<VecDeque<Delivery>>,
+    ready: Condvar,
+}
+
+#[derive(Clone)]
+enum Delivery {
+    Publish(Arc<PlotMessage>),
+    /// Replace the upstream copy's annotations with this plot's
+    Annotate(Arc<PlotMessage>),
+}
+
+impl Delivery {
+    fn msg(&self) -> &Arc<PlotMessage> {
+        match self {
+            Delivery::Publish(msg) | Delivery::Annotate(msg) => msg,
+        }
+    }
+
+    fn is(&self, other: &Delivery) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+            && Arc::ptr_eq(self.msg(), other.msg())
+    }
+}
+
+/// The upstream's `PATCH /api/plots/:id` body.
+#[derive(Serialize)]
+struct Annotate<'a> {
+    token: Option<&'a str>,
+    annotations: &'a std::collections::HashMap<String, String>,
+}
+
+/// The upstream's `/api/publish` body, carrying our id and timestamp.
+#[derive(Serialize)]
+struct Forward<'a> {
+    token: Option<&'a str>,
+    id: &'a str,
+    timestamp: u64,
+    content: &'a rv_core::PlotContent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_at: Option<u64>,
+    pinned: bool,
+    priority: i32,
+    annotations: &'a std::collections::HashMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group_title: Option<&'a str>,
+}
+
+impl Upstream {
+    /// Start the forwarding thread for `config`.
+    pub(crate) fn spawn(config: UpstreamConfig) -> anyhow::Result<Self> {
+        let queue = Arc::new(Queue::default());
+        let worker = queue.clone();
+        std::thread::Builder::new()
+            .name("rv-upstream".into())
+            .spawn(move || worker.forward_all(&config))
+            .context("failed to spawn upstream thread")?;
+        Ok(Self { queue })
+    }
+
+    /// Queue a plot for forwarding without blocking.
+    pub(crate) fn send(&self, msg: &Arc<PlotMessage>) {
+        self.queue(Delivery::Publish(msg.clone()));
+    }
+
+    /// Queue `msg`'s new annotations for the upstream copy without blocking.
+    pub(crate) fn annotate(&self, msg: &Arc<PlotMessage>) {
+        self.queue(Delivery::Annotate(msg.clone()));
+    }
+
+    fn queue(&self, delivery: Delivery) {
+        let mut plots = self.queue.plots.lock().unwrap();
+        if plots.len() == BUFFER_LIMIT {
+            if let Some(dropped) = plots.pop_front() {
+                warn!("Upstream buffer full, dropping plot {}", dropped.msg().id);
+            }
+        }
+        plots.push_back(delivery);
+        self.queue.ready.notify_one();
+    }
+}
+
+impl Queue {
+    fn forward_all(&self, config: &UpstreamConfig) {
+        let agent = ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build();
+        let base = config.url.trim_end_matches('/');
+        let mut backoff = INITIAL_BACKOFF;
+        let mut down = false;
+        loop {
+            let delivery = {
+                let plots = self.plots.lock().unwrap();
+                let plots = self.ready.wait_while(plots, |p| p.is_empty()).unwrap();
+                plots.front().cloned()
+            };
+            let Some(delivery) = delivery else { continue };
+            let msg = delivery.msg();
+            let token = config.token.as_deref();
+            let result = match &delivery {
+                Delivery::Publish(msg) => forward(&agent, base, token, msg),
+                Delivery::Annotate(msg) => annotate(&agent, base, token, msg),
+            };
+            match result {
+                Ok(()) => {
+                    if down {
+                        info!("Upstream {} reachable again", config.url);
+                        down = false;
+                    }
+                    backoff = INITIAL_BACKOFF;
+                    self.remove(&delivery);
+                }
+                // 4xx won't succeed on retry, so don't hold up the plots behind it
+                Err(Failure::Rejected(code)) => {
+                    warn!("Upstream rejected plot {} with HTTP {}", msg.id, code);
+                    self.remove(&delivery);
+                }
+                Err(Failure::Unsendable(e)) => {
+                    warn!("Dropping plot {} for upstream: {}", msg.id, e);
+                    self.remove(&delivery);
+                }
+                Err(Failure::Unreachable(e)) => {
+                    if !down {
+                        warn!("Upstream {} unreachable, buffering: {}", config.url, e);
+                        down = true;
+                    } else {
+                        debug!("Upstream still unreachable: {}", e);
+                    }
+                    std::thread::sleep(backoff);
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+    }
+
+    /// Drop `delivery` from the front unless the buffer already overflowed past it.
+    fn remove(&self, delivery: &Delivery) {
+        let mut plots = self.plots.lock().unwrap();
+        if plots.front().is_some_and(|front| front.is(delivery)) {
+            plots.pop_front();
+        }
+    }
+}
+
+enum Failure {
+    Rejected(u16),
+    /// Couldn't be encoded, so no retry will get it through
+    Unsendable(String),
+    /// Down, or refusing for now (e.g. `require_client` with no viewer)
+    Unreachable(String),
+}
+
+fn forward(
+    agent: &ureq::Agent,
+    base: &str,
+    token: Option<&str>,
+    msg: &PlotMessage,
+) -> Result<(), Failure> {
+    let body = Forward {
+        token,
+        id: &msg.id,
+        timestamp: msg.timestamp,
+        content: &msg.content,
+        expires_at: msg.expires_at,
+        pinned: msg.pinned,
+        priority: msg.priority,
+        annotations: &msg.annotations,
+        group_id: msg.group_id.as_deref(),
+        group_title: msg.group_title.as_deref(),
+    };
+    let body = serde_json::to_string(&body).map_err(|e| Failure::Unsendable(e.to_string()))?;
+    let request = agent
+        .post(&format!("{base}/api/publish"))
+        .set("Content-Type", "application/json");
+    match request.send_string(&body) {
+        Ok(_) => Ok(()),
+        Err(ureq::Error::Status(409, response)) => {
+            // Already there, e.g. resent after a response was lost
+            if response.header(crate::ERROR_CODE) == Some(crate::DUPLICATE_ID) {
+                return Ok(());
+            }
+            let reason = response.into_string().unwrap_or_default();
+            Err(Failure::Unreachable(format!("HTTP 409 {reason}")))
+        }
+        Err(ureq::Error::Status(code, _)) if (400..500).contains(&code) => {
+            Err(Failure::Rejected(code))
+        }
+        Err(e) => Err(Failure::Unreachable(e.to_string())),
+    }
+}
+
+fn annotate(
+    agent: &ureq::Agent,
+    base: &str,
+    token: Option<&str>,
+    msg: &PlotMessage,
+) -> Result<(), Failure> {
+    let body = Annotate {
+        token,
+        annotations: &msg.annotations,
+    };
+    let body = serde_json::to_string(&body).map_err(|e| Failure::Unsendable(e.to_string()))?;
+    let url = format!("{base}/api/plots/{}", path_segment(&msg.id));
+    let request = agent
+        .request("PATCH", &url)
+        .set("Content-Type", "application/json");
+    match request.send_string(&body) {
+        Ok(_) => Ok(()),
+        // Includes 404: the upstream dropped the plot or never got it
+        Err(ureq::Error::Status(code, _)) if (400..500).contains(&code) => {
+            Err(Failure::Rejected(code))
+        }
+        Err(e) => Err(Failure::Unreachable(e.to_string())),
+    }
+}
+
+/// Percent-encode a plot id for use as one URL path segment.
+fn path_segment(id: &str) -> String {
+    id.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                char::from(b).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
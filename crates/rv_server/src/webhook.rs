@@ -0,0 +1,89 @@
+//! Best-effort mirroring of published plots to an external URL.
+//!
+//! Plots are handed to a background thread over a bounded queue, so a slow or
+//! unreachable endpoint never holds up a publish: when the queue is full the
+//! plot is skipped for the webhook and a warning is logged.
+
+use std::{
+    sync::{mpsc, Arc},
+    time::Duration,
+};
+
+use anyhow::Context;
+use rv_core::PlotMessage;
+use tracing::{debug, warn};
+
+/// Plots waiting to be posted before new ones are dropped.
+const QUEUE_DEPTH: usize = 256;
+/// Per-request deadline, connect through response.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// Attempts per plot, with backoff doubling from [`RETRY_BACKOFF`].
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_BACKOFF: Duration = Duration::from_millis(200);
+
+#[derive(Clone)]
+pub(crate) struct Webhook {
+    tx: mpsc::SyncSender<Arc<PlotMessage>>,
+}
+
+impl Webhook {
+    /// Start the posting thread for `url`, authenticating with `token` as a bearer token.
+    pub(crate) fn spawn(url: String, token: Option<String>) -> anyhow::Result<Self> {
+        let (tx, rx) = mpsc::sync_channel::<Arc<PlotMessage>>(QUEUE_DEPTH);
+        let agent = ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build();
+        std::thread::Builder::new()
+            .name("rv-webhook".into())
+            .spawn(move || {
+                for msg in rx {
+                    if let Err(e) = post(&agent, &url, token.as_deref(), &msg) {
+                        warn!("Webhook failed for plot {}: {:#}", msg.id, e);
+                    }
+                }
+            })
+            .context("failed to spawn webhook thread")?;
+        Ok(Self { tx })
+    }
+
+    /// Queue a plot for posting without blocking.
+    pub(crate) fn send(&self, msg: &Arc<PlotMessage>) {
+        if let Err(mpsc::TrySendError::Full(msg)) = self.tx.try_send(msg.clone()) {
+            warn!("Webhook queue full, not mirroring plot {}", msg.id);
+        }
+    }
+}
+
+fn post(
+    agent: &ureq::Agent,
+    url: &str,
+    token: Option<&str>,
+    msg: &PlotMessage,
+) -> anyhow::Result<()> {
+    let body = serde_json::to_string(msg)?;
+    let mut backoff = RETRY_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        let mut request = agent.post(url).set("Content-Type", "application/json");
+        if let Some(token) = token {
+            request = request.set("Authorization", &format!("Bearer {token}"));
+        }
+        match request.send_string(&body) {
+            Ok(_) => return Ok(()),
+            // 4xx won't succeed on retry
+            Err(ureq::Error::Status(code, _)) if (400..500).contains(&code) => {
+                anyhow::bail!("{url} rejected the plot with HTTP {code}");
+            }
+            Err(e) if attempt == MAX_ATTEMPTS => {
+                return Err(e).with_context(|| format!("gave up after {MAX_ATTEMPTS} attempts"));
+            }
+            Err(e) => {
+                debug!(
+                    "Webhook attempt {} for plot {} failed: {}",
+                    attempt, msg.id, e
+                );
+                std::thread::sleep(backoff);
+                backoff *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
@@ -1,6 +1,8 @@
-from .viewer import MatplotlibContext, Viewer
+from .aio import AsyncViewer
+from .viewer import Group, MatplotlibContext, PublishResult, Viewer, verify_signature
 from .exceptions import (
     CLINotFoundError,
+    NoClientsError,
     RileyViewerError,
     ServerConnectionError,
     ServerStartError,
@@ -10,8 +12,13 @@ from .exceptions import (
 
 __all__ = [
     "Viewer",
+    "AsyncViewer",
     "MatplotlibContext",
+    "Group",
+    "PublishResult",
+    "verify_signature",
     "CLINotFoundError",
+    "NoClientsError",
     "RileyViewerError",
     "ServerConnectionError",
     "ServerStartError",
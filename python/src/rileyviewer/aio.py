@@ -0,0 +1,111 @@
+"""Async access to the viewer for notebooks and other event-loop code.
+
+Publishing is blocking HTTP, so each call runs on a worker thread and is
+awaited; the event loop keeps running while plots upload.
+"""
+
+from __future__ import annotations
+
+import asyncio
+from typing import Any, Callable, Iterable, Optional, TypeVar, Union
+
+from .adapters import MatplotlibFormat
+from .viewer import Group, PublishResult, Viewer, check_content
+
+T = TypeVar("T")
+
+
+class AsyncViewer:
+    """A Viewer whose sends are awaitable, used as an async context manager.
+
+        async with AsyncViewer() as v:
+            ids = await v.send_many([
+                {"type": "Svg", "data": loss_svg},
+                {"type": "Svg", "data": accuracy_svg},
+            ])
+
+    Takes the same arguments as Viewer. Leaving the block waits for every
+    send started inside it, including ones that were never awaited.
+    """
+
+    def __init__(self, *args: Any, **kwargs: Any) -> None:
+        self._args = args
+        self._kwargs = kwargs
+        self._viewer: Optional[Viewer] = None
+        self._pending: set[asyncio.Future] = set()
+
+    async def __aenter__(self) -> "AsyncViewer":
+        # Attaching may spawn the server and wait for it to come up
+        self._viewer = await asyncio.to_thread(Viewer, *self._args, **self._kwargs)
+        return self
+
+    async def __aexit__(self, exc_type, exc_val, exc_tb) -> None:
+        await self.flush()
+
+    @property
+    def viewer(self) -> Viewer:
+        """The underlying synchronous Viewer."""
+        if self._viewer is None:
+            raise RuntimeError("AsyncViewer must be entered with `async with` first")
+        return self._viewer
+
+    def _run(self, func: Callable[..., T], *args: Any, **kwargs: Any) -> "asyncio.Future[T]":
+        future = asyncio.ensure_future(asyncio.to_thread(func, *args, **kwargs))
+        self._pending.add(future)
+        future.add_done_callback(self._pending.discard)
+        return future
+
+    async def show(self, obj: Any, format: Optional[MatplotlibFormat] = None) -> str:
+        """Serialize a plotting object and send it, as Viewer.show."""
+        return await self._run(self.viewer.show, obj, format=format)
+
+    async def publish(
+        self,
+        content: dict,
+        with_timestamp: bool = False,
+        ttl: Optional[float] = None,
+        pin: bool = False,
+        metadata: Optional[dict[str, str]] = None,
+        group: Union[str, Group, None] = None,
+        priority: int = 0,
+    ) -> Union[str, PublishResult]:
+        """Send content in the server's JSON form, as Viewer.publish."""
+        return await self._run(
+            self.viewer.publish, content, with_timestamp, ttl, pin, metadata, group, priority
+        )
+
+    async def send_many(
+        self,
+        contents: Iterable[dict],
+        ttl: Optional[float] = None,
+        pin: bool = False,
+        metadata: Optional[dict[str, str]] = None,
+        group: Union[str, Group, None] = None,
+        priority: int = 0,
+    ) -> list[str]:
+        """Publish several contents at once and return their ids, in order.
+
+        Every item is checked before any is sent, so a malformed one raises
+        ValueError without publishing the rest. The sends then run
+        concurrently, bounded by the viewer's ``max_in_flight``; if one fails
+        its error is raised once the others have finished.
+        """
+        contents = list(contents)
+        for content in contents:
+            check_content(content)
+        sends = [
+            self._run(self.viewer.publish, content, False, ttl, pin, metadata, group, priority)
+            for content in contents
+        ]
+        results = await asyncio.gather(*sends, return_exceptions=True)
+        for result in results:
+            if isinstance(result, BaseException):
+                raise result
+        return results
+
+    async def flush(self) -> None:
+        """Wait for every send started on this viewer to complete."""
+        while self._pending:
+            await asyncio.gather(*self._pending, return_exceptions=True)
+        if self._viewer is not None:
+            await asyncio.to_thread(self._viewer.flush)
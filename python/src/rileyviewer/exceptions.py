@@ -15,6 +15,15 @@ class ServerConnectionError(RileyViewerError):
     pass
 
 
+class NoClientsError(ServerConnectionError):
+    """The server rejected a publish because no viewer is connected.
+
+    Only raised when the server runs with ``--require-client``.
+    """
+
+    pass
+
+
 class ServerStartError(RileyViewerError):
     """Failed to start the rileyviewer server."""
 
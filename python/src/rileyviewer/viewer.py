@@ -1,23 +1,98 @@
 from __future__ import annotations
 
 import base64
+import contextlib
+import hashlib
+import hmac
 import json
 import os
 import subprocess
 import sys
+import threading
 import time
 import urllib.request
 import urllib.error
+import uuid
 from pathlib import Path
-from typing import Any, Literal, Optional
+from typing import Any, Iterator, Literal, NamedTuple, Optional, Union
 
 from . import adapters
 from .adapters import MatplotlibFormat
-from .exceptions import CLINotFoundError, ServerConnectionError, ServerStartError
+from .exceptions import (
+    CLINotFoundError,
+    NoClientsError,
+    ServerConnectionError,
+    ServerStartError,
+)
 
 DEFAULT_PORT = 7878
 DEFAULT_HOST = "127.0.0.1"
 
+def _sniff_image_type(data: bytes) -> Optional[str]:
+    """Map an image's magic bytes to its PlotContent type name."""
+    if data.startswith(b"\x89PNG\r\n\x1a\n"):
+        return "Png"
+    if data.startswith(b"\xff\xd8\xff"):
+        return "Jpeg"
+    if data[:4] == b"RIFF" and data[8:12] == b"WEBP":
+        return "Webp"
+    if data[4:12] == b"ftypavif":
+        return "Avif"
+    if data[:6] in (b"GIF87a", b"GIF89a"):
+        return "Gif"
+    return None
+
+
+def verify_signature(message: dict, key: str) -> bool:
+    """Check a plot message's ``sig`` from a server started with a signing key.
+
+    ``message`` is the decoded JSON of a plot as sent over /ws or /api/history.
+    """
+    sig = message.get("sig")
+    if not isinstance(sig, str):
+        return False
+    parts = [message["id"], *_signing_parts(message["content"])]
+    payload = "".join(f"{part}\n" for part in parts).encode("utf-8")
+    expected = hmac.new(key.encode("utf-8"), payload, hashlib.sha256).hexdigest()
+    return hmac.compare_digest(expected, sig)
+
+
+def _signing_parts(content: dict) -> list[str]:
+    """A content's signed fields in order, recursing into Compare panes."""
+    data = content["data"]
+    if content["type"] == "Custom":
+        return ["Custom", data["renderer"], data["data"]]
+    if content["type"] == "Compare":
+        panes = _signing_parts(data["left"]) + _signing_parts(data["right"])
+        return ["Compare", *data["labels"], *panes]
+    return [content["type"], data]
+
+
+def check_content(content: dict) -> None:
+    """Raise ValueError unless ``content`` has the shape of a PlotContent.
+
+    Which types exist is left to the server, which rejects unknown ones.
+    """
+    kind = content.get("type") if isinstance(content, dict) else None
+    if not isinstance(kind, str):
+        raise ValueError(f"content type must be a string, not {kind!r}")
+    if "data" not in content:
+        raise ValueError(f"{kind} content needs data")
+
+
+class Group(NamedTuple):
+    """A labeled set of plots that the viewer shows together, like one report."""
+
+    id: str
+    title: str
+
+
+class PublishResult(NamedTuple):
+    """The server-assigned identity of a published plot."""
+
+    id: str
+    timestamp: int  # Unix milliseconds, as stored by the server
+
 
 def _state_dir() -> Path:
     """Get the rileyviewer state directory."""
@@ -30,9 +105,17 @@ def _state_dir() -> Path:
     return base / "rileyviewer"
 
 
+def _state_file() -> Path:
+    """Get the server state file path, honoring RILEYVIEWER_STATE_FILE."""
+    override = os.environ.get("RILEYVIEWER_STATE_FILE")
+    if override:
+        return Path(override)
+    return _state_dir() / "server.json"
+
+
 def _read_server_state() -> Optional[dict]:
     """Read the server state file if it exists."""
-    state_file = _state_dir() / "server.json"
+    state_file = _state_file()
     if state_file.exists():
         try:
             return json.loads(state_file.read_text())
@@ -42,11 +125,16 @@ def _read_server_state() -> Optional[dict]:
 
 
 def _check_server_running(host: str, port: int) -> bool:
-    """Check if a server is running on the given host:port."""
+    """Check if a server is running on the given host:port.
+
+    A draining server (503) still counts: it holds the port until it stops.
+    """
     try:
         url = f"http://{host}:{port}/health"
         with urllib.request.urlopen(url, timeout=0.5) as resp:
             return resp.status == 200
+    except urllib.error.HTTPError as e:
+        return e.code == 503
     except (urllib.error.URLError, TimeoutError, OSError):
         return False
 
@@ -145,7 +233,10 @@ class Viewer:
         dist_dir: Optional[str] = None,
         history_limit: Optional[int] = None,
         default_format: MatplotlibFormat = "svg",
+        max_in_flight: int = 256,
     ) -> None:
+        if max_in_flight < 1:
+            raise ValueError("max_in_flight must be at least 1")
         self._host = host or DEFAULT_HOST
         self._port = port if port is not None else DEFAULT_PORT
         self._token = token
@@ -153,7 +244,17 @@ class Viewer:
         self._dist_dir = dist_dir
         self._history_limit = history_limit
         self._default_format: MatplotlibFormat = default_format
-
+        # Publishes in progress across threads, so flush() can wait them out;
+        # senders block once max_in_flight are pending
+        self._in_flight = 0
+        self._max_in_flight = max_in_flight
+        self._idle = threading.Condition()
+        # Groups opened with group(), innermost last, per thread
+        self._groups = threading.local()
+        self._ensure_server()
+
+    def _ensure_server(self) -> None:
+        """Attach to the server on host:port, spawning one if none answers."""
         # Check if server already running
         if _check_server_running(self._host, self._port):
             # Server running - read token from state file if we don't have one
@@ -183,6 +284,21 @@ class Viewer:
             if state and state.get("addr") == f"{self._host}:{self._port}":
                 self._token = state.get("token")
 
+    def is_alive(self) -> bool:
+        """Whether the server answers its health check."""
+        return _check_server_running(self._host, self._port)
+
+    def restart(self) -> None:
+        """Start a fresh server with this viewer's original settings if it has gone away.
+
+        A server that is still running is left alone. The respawned server
+        is given the token this viewer already holds, so other clients using
+        it keep working.
+        """
+        if self.is_alive():
+            return
+        self._ensure_server()
+
     @property
     def addr(self) -> str:
         return f"{self._host}:{self._port}"
@@ -192,16 +308,45 @@ class Viewer:
         return self._token
 
     def _http_publish(self, content: dict, max_retries: int = 3) -> str:
+        """Publish via HTTP POST and return the assigned plot ID."""
+        return self._http_publish_result(content, max_retries).id
+
+    def _http_publish_result(
+        self,
+        content: dict,
+        max_retries: int = 3,
+        expires_at: Optional[int] = None,
+        pinned: bool = False,
+        metadata: Optional[dict[str, str]] = None,
+        group: Optional[Group] = None,
+        priority: int = 0,
+    ) -> PublishResult:
         """Publish via HTTP POST with retry logic for transient failures."""
         url = f"http://{self._host}:{self._port}/api/publish"
-        payload = {"content": content}
+        payload: dict[str, Any] = {"content": content}
         if self._token:
             payload["token"] = self._token
+        if expires_at is not None:
+            payload["expires_at"] = expires_at
+        if pinned:
+            payload["pinned"] = True
+        if priority:
+            payload["priority"] = priority
+        if metadata:
+            payload["annotations"] = metadata
+        if group is not None:
+            payload["group_id"] = group.id
+            payload["group_title"] = group.title
         data = json.dumps(payload).encode("utf-8")
+        # The same key on every attempt, so a retry after a lost response
+        # gets the stored plot back rather than publishing it twice
         req = urllib.request.Request(
             url,
             data=data,
-            headers={"Content-Type": "application/json"},
+            headers={
+                "Content-Type": "application/json",
+                "Idempotency-Key": uuid.uuid4().hex,
+            },
             method="POST",
         )
 
@@ -210,10 +355,20 @@ class Viewer:
             try:
                 with urllib.request.urlopen(req, timeout=5) as resp:
                     result = json.loads(resp.read().decode("utf-8"))
-                    return result["id"]
+                    return PublishResult(result["id"], result["timestamp"])
             except urllib.error.HTTPError as e:
+                body = e.read().decode("utf-8", errors="replace")
+                # An earlier attempt still being stored is worth asking again
+                in_progress = e.headers.get("Error-Code") == "idempotency-in-progress"
+                # Unknown type or malformed data; the body says which
+                if e.code in (400, 422):
+                    raise ValueError(f"Server rejected content: {body}") from e
+                if e.code == 409 and not in_progress:
+                    raise NoClientsError(
+                        "Server rejected plot: no viewer connected (started with --require-client)"
+                    ) from e
                 # Don't retry client errors (4xx) - they won't succeed
-                if 400 <= e.code < 500:
+                if 400 <= e.code < 500 and not in_progress:
                     raise ServerConnectionError(
                         f"Server rejected request: HTTP {e.code} {e.reason}"
                     ) from e
@@ -253,33 +408,250 @@ class Viewer:
         """
         return adapters.send_object_http(self, obj, format=format)
 
-    def send_png_bytes(self, data: bytes) -> str:
+    def _send(
+        self,
+        content: dict,
+        with_timestamp: bool,
+        ttl: Optional[float] = None,
+        pin: bool = False,
+        metadata: Optional[dict[str, str]] = None,
+        group: Union[str, Group, None] = None,
+        priority: int = 0,
+    ) -> Union[str, PublishResult]:
+        # The server drops the plot once this absolute time (ms) passes
+        expires_at = int((time.time() + ttl) * 1000) if ttl is not None else None
+        with self._idle:
+            self._idle.wait_for(lambda: self._in_flight < self._max_in_flight)
+            self._in_flight += 1
+        try:
+            result = self._http_publish_result(
+                content,
+                expires_at=expires_at,
+                pinned=pin,
+                metadata=metadata,
+                group=self._resolve_group(group),
+                priority=priority,
+            )
+        finally:
+            with self._idle:
+                self._in_flight -= 1
+                # Wakes both flush() and senders waiting for a free slot
+                self._idle.notify_all()
+        return result if with_timestamp else result.id
+
+    def _resolve_group(self, group: Union[str, Group, None]) -> Optional[Group]:
+        if isinstance(group, str):
+            return Group(group, group)
+        if group is not None:
+            return group
+        groups = getattr(self._groups, "stack", None)
+        return groups[-1] if groups else None
+
+    @contextlib.contextmanager
+    def group(self, title: str) -> Iterator[Group]:
+        """Group every plot sent from this thread inside the block under ``title``.
+
+        Each block is a new group, even if an earlier one had the same title.
+        An explicit ``group=`` on a send still wins.
+
+            with viewer.group("Model comparison"):
+                viewer.show(fig_loss)
+                viewer.show(fig_accuracy)
+        """
+        group = Group(uuid.uuid4().hex, title)
+        stack = self._groups.__dict__.setdefault("stack", [])
+        stack.append(group)
+        try:
+            yield group
+        finally:
+            stack.pop()
+
+    def flush(self, timeout: Optional[float] = None) -> bool:
+        """Wait until every publish started on this viewer (from any thread) has completed.
+
+        Returns False if ``timeout`` seconds elapse first.
+        """
+        with self._idle:
+            return self._idle.wait_for(lambda: self._in_flight == 0, timeout)
+
+    # The send_* methods return the plot ID, or a PublishResult with the
+    # server-assigned timestamp when called with with_timestamp=True.
+    # Pass ttl (seconds) to have the server remove the plot after that long,
+    # or pin=True to keep it from being evicted by the history limit.
+    # metadata attaches string key/value annotations (run id, git commit, ...)
+    # that are stored and broadcast with the plot. group files the plot under a
+    # report: a title string, or a Group from group(). priority ranks the plot
+    # in /api/history?sort=priority (higher first) without changing its place
+    # in the live stream.
+
+    def send_png_bytes(
+        self,
+        data: bytes,
+        with_timestamp: bool = False,
+        ttl: Optional[float] = None,
+        pin: bool = False,
+        metadata: Optional[dict[str, str]] = None,
+        group: Union[str, Group, None] = None,
+        priority: int = 0,
+    ) -> Union[str, PublishResult]:
         """Send raw PNG bytes to the server."""
         encoded = base64.b64encode(data).decode("ascii")
-        return self._http_publish({"type": "Png", "data": encoded})
+        return self._send({"type": "Png", "data": encoded}, with_timestamp, ttl, pin, metadata, group, priority)
 
-    def send_svg(self, svg: str) -> str:
+    def send_image(
+        self,
+        data: bytes,
+        with_timestamp: bool = False,
+        ttl: Optional[float] = None,
+        pin: bool = False,
+        metadata: Optional[dict[str, str]] = None,
+        group: Union[str, Group, None] = None,
+        priority: int = 0,
+    ) -> Union[str, PublishResult]:
+        """Send raster image bytes, detecting PNG/JPEG/WebP/AVIF/GIF from the header.
+
+        Raises:
+            ValueError: If the bytes aren't a recognized image format.
+        """
+        content_type = _sniff_image_type(data)
+        if content_type is None:
+            raise ValueError("Unrecognized image format (expected PNG, JPEG, WebP, AVIF, or GIF)")
+        encoded = base64.b64encode(data).decode("ascii")
+        return self._send({"type": content_type, "data": encoded}, with_timestamp, ttl, pin, metadata, group, priority)
+
+    def send_svg(
+        self,
+        svg: str,
+        with_timestamp: bool = False,
+        ttl: Optional[float] = None,
+        pin: bool = False,
+        metadata: Optional[dict[str, str]] = None,
+        group: Union[str, Group, None] = None,
+        priority: int = 0,
+    ) -> Union[str, PublishResult]:
         """Send raw SVG string to the server."""
-        return self._http_publish({"type": "Svg", "data": svg})
+        return self._send({"type": "Svg", "data": svg}, with_timestamp, ttl, pin, metadata, group, priority)
 
-    def send_plotly_json(self, payload: str) -> str:
+    def send_plotly_json(
+        self,
+        payload: str,
+        with_timestamp: bool = False,
+        ttl: Optional[float] = None,
+        pin: bool = False,
+        metadata: Optional[dict[str, str]] = None,
+        group: Union[str, Group, None] = None,
+        priority: int = 0,
+    ) -> Union[str, PublishResult]:
         """Send Plotly JSON to the server."""
-        return self._http_publish({"type": "Plotly", "data": payload})
+        return self._send({"type": "Plotly", "data": payload}, with_timestamp, ttl, pin, metadata, group, priority)
 
-    def send_vega_json(self, payload: str) -> str:
+    def send_vega_json(
+        self,
+        payload: str,
+        with_timestamp: bool = False,
+        ttl: Optional[float] = None,
+        pin: bool = False,
+        metadata: Optional[dict[str, str]] = None,
+        group: Union[str, Group, None] = None,
+        priority: int = 0,
+    ) -> Union[str, PublishResult]:
         """Send Vega/Vega-Lite JSON to the server."""
-        return self._http_publish({"type": "Vega", "data": payload})
+        return self._send({"type": "Vega", "data": payload}, with_timestamp, ttl, pin, metadata, group, priority)
 
-    def send_html(self, html: str) -> str:
+    def send_html(
+        self,
+        html: str,
+        with_timestamp: bool = False,
+        ttl: Optional[float] = None,
+        pin: bool = False,
+        metadata: Optional[dict[str, str]] = None,
+        group: Union[str, Group, None] = None,
+        priority: int = 0,
+    ) -> Union[str, PublishResult]:
         """Send raw HTML to the server."""
-        return self._http_publish({"type": "Html", "data": html})
+        return self._send({"type": "Html", "data": html}, with_timestamp, ttl, pin, metadata, group, priority)
+
+    def send_custom(
+        self,
+        renderer: str,
+        data: str,
+        with_timestamp: bool = False,
+        ttl: Optional[float] = None,
+        pin: bool = False,
+        metadata: Optional[dict[str, str]] = None,
+        group: Union[str, Group, None] = None,
+        priority: int = 0,
+    ) -> Union[str, PublishResult]:
+        """Send a payload for a custom frontend renderer.
+
+        Args:
+            renderer: Name of the frontend renderer plugin to dispatch to.
+            data: The JSON (or other string) payload passed to the renderer.
+        """
+        content = {"type": "Custom", "data": {"renderer": renderer, "data": data}}
+        return self._send(content, with_timestamp, ttl, pin, metadata, group, priority)
+
+    def send_compare(
+        self,
+        left: dict,
+        right: dict,
+        labels: tuple[str, str] = ("A", "B"),
+        with_timestamp: bool = False,
+        ttl: Optional[float] = None,
+        pin: bool = False,
+        metadata: Optional[dict[str, str]] = None,
+        group: Union[str, Group, None] = None,
+        priority: int = 0,
+    ) -> Union[str, PublishResult]:
+        """Send two plots to be shown side by side, captioned with ``labels``.
+
+        Args:
+            left: Content for the left pane, e.g. ``{"type": "Svg", "data": svg}``.
+            right: Content for the right pane, in the same form.
+            labels: Captions for the left and right panes.
+
+        Raises:
+            ValueError: If a pane is missing its type or data, or is itself a comparison.
+        """
+        for side, pane in (("left", left), ("right", right)):
+            if not pane or "type" not in pane or not pane.get("data"):
+                raise ValueError(f"{side} pane needs a type and data")
+            if pane["type"] == "Compare":
+                raise ValueError(f"{side} pane can't be another comparison")
+        content = {
+            "type": "Compare",
+            "data": {"left": left, "right": right, "labels": list(labels)},
+        }
+        return self._send(content, with_timestamp, ttl, pin, metadata, group, priority)
+
+    def publish(
+        self,
+        content: dict,
+        with_timestamp: bool = False,
+        ttl: Optional[float] = None,
+        pin: bool = False,
+        metadata: Optional[dict[str, str]] = None,
+        group: Union[str, Group, None] = None,
+        priority: int = 0,
+    ) -> Union[str, PublishResult]:
+        """Send content in the server's own JSON form, e.g. ``{"type": "Svg", "data": svg}``.
+
+        A single entrypoint for content built programmatically; the ``send_*``
+        methods all reduce to this shape. Image data must already be base64.
+
+        Raises:
+            ValueError: If ``type`` or ``data`` is missing, or the server
+                rejects the content, e.g. for an unknown type.
+        """
+        check_content(content)
+        return self._send(content, with_timestamp, ttl, pin, metadata, group, priority)
 
     def capture(self) -> "MatplotlibContext":
         return MatplotlibContext(self)
 
     def shutdown(self) -> None:
-        """Shutdown is now a no-op for detached servers. Use `rileyviewer stop` CLI."""
-        pass
+        """Wait for pending publishes; the detached server keeps running (use `rileyviewer stop`)."""
+        self.flush()
 
 
 class MatplotlibContext: